@@ -0,0 +1,232 @@
+//! 合成订单流, criterion及延迟统计共用
+#![allow(dead_code)]
+use match_trade::{Engine, Order, OrderKind, OrderSide};
+use rust_decimal::Decimal;
+
+/// 中间价100.00, 单位为tick(0.01)
+pub const MID: i64 = 10000;
+
+/// 订单流中的一个操作
+///
+/// 操作预先生成后按值执行, 不装箱以免在计时内释放内存
+#[allow(clippy::large_enum_variant)]
+pub enum Op {
+    Add(Order),
+    Cancel {
+        symbol: String,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+    },
+}
+
+impl Op {
+    /// 统计延迟时使用的操作名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Add(order) => match order.kind {
+                OrderKind::Market => "market",
+                OrderKind::IOC => "ioc",
+                OrderKind::FOK => "fok",
+                _ => "limit",
+            },
+            Op::Cancel { .. } => "cancel",
+        }
+    }
+
+    pub fn apply(self, engine: &Engine) {
+        match self {
+            Op::Add(order) => {
+                let _ = engine.add_order(order);
+            }
+            Op::Cancel {
+                symbol,
+                order_id,
+                price,
+                side,
+            } => {
+                engine.cancel_order(&symbol, order_id, price, side);
+            }
+        }
+    }
+}
+
+/// 订单流参数
+#[derive(Clone, Copy)]
+pub struct FlowConfig {
+    pub name: &'static str,
+    pub symbols: usize,
+    /// 初始每边价位数
+    pub levels: i64,
+    /// 初始每个价位订单数
+    pub per_level: u64,
+    /// 操作数
+    pub ops: usize,
+    /// 撤单占比, 没有可撤挂单时改为新挂单
+    pub cancel_ratio: f64,
+    /// 扫单占比, 扫单为IOC单, 市价单只吃对手盘第一档且剩余部分会挂单
+    pub market_ratio: f64,
+    /// 扫单限价距中间价的价位数, 最多吃掉的价位数
+    pub sweep_levels: i64,
+}
+
+/// 深订单簿, 以挂单为主
+pub const DEEP_BOOK: FlowConfig = FlowConfig {
+    name: "deep_book",
+    symbols: 1,
+    levels: 2000,
+    per_level: 20,
+    ops: 200_000,
+    cancel_ratio: 0.3,
+    market_ratio: 0.02,
+    sweep_levels: 1,
+};
+
+/// 高撤单率, 做市商频繁改价, 几乎所有新挂单最终被撤销
+pub const CANCEL_HEAVY: FlowConfig = FlowConfig {
+    name: "cancel_heavy",
+    symbols: 1,
+    levels: 200,
+    per_level: 10,
+    ops: 200_000,
+    cancel_ratio: 0.48,
+    market_ratio: 0.01,
+    sweep_levels: 1,
+};
+
+/// 大额IOC单扫过多个价位
+pub const MARKET_SWEEP: FlowConfig = FlowConfig {
+    name: "market_sweep",
+    symbols: 1,
+    levels: 500,
+    per_level: 10,
+    ops: 100_000,
+    cancel_ratio: 0.1,
+    market_ratio: 0.02,
+    sweep_levels: 10,
+};
+
+/// 多标的
+pub const MULTI_SYMBOL: FlowConfig = FlowConfig {
+    name: "multi_symbol",
+    symbols: 64,
+    levels: 50,
+    per_level: 5,
+    ops: 200_000,
+    cancel_ratio: 0.4,
+    market_ratio: 0.05,
+    sweep_levels: 2,
+};
+
+pub const FLOWS: [FlowConfig; 4] = [DEEP_BOOK, CANCEL_HEAVY, MARKET_SWEEP, MULTI_SYMBOL];
+
+/// xorshift64*, 固定种子保证每次生成相同的订单流
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        return Rng(seed.max(1));
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        return self.0.wrapping_mul(0x2545_F491_4F6C_DD1D);
+    }
+
+    /// [0, n)
+    pub fn below(&mut self, n: u64) -> u64 {
+        return self.next() % n;
+    }
+
+    /// [0, 1)
+    pub fn unit(&mut self) -> f64 {
+        return (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+    }
+}
+
+fn symbol(index: usize) -> String {
+    return format!("SYM{}/USDT", index);
+}
+
+fn limit(id: u64, symbol: &str, tick: i64, volumn: i64, side: OrderSide) -> Order {
+    return Order::new(
+        id,
+        symbol,
+        Decimal::new(tick, 2),
+        Decimal::new(volumn, 0),
+        OrderKind::Limit,
+        side,
+        0,
+    )
+    .with_owner(id % 100);
+}
+
+/// 生成的订单流, engine已挂好初始订单
+pub struct Flow {
+    pub engine: Engine,
+    pub ops: Vec<Op>,
+}
+
+impl FlowConfig {
+    /// 挂单价格不与对手盘交叉, 只有扫单成交
+    pub fn build(&self, seed: u64) -> Flow {
+        let engine = Engine::new();
+        let mut rng = Rng::new(seed);
+        let mut id = 0;
+        // 各标的挂单 (order_id, tick, side), 用于随机撤单
+        let mut resting: Vec<Vec<(u64, i64, OrderSide)>> = vec![Vec::new(); self.symbols];
+        for (index, orders) in resting.iter_mut().enumerate() {
+            let symbol = symbol(index);
+            engine.add_book(&symbol, None).unwrap();
+            for level in 1..=self.levels {
+                for _ in 0..self.per_level {
+                    for (side, tick) in [(OrderSide::Buy, MID - level), (OrderSide::Sell, MID + level)] {
+                        id += 1;
+                        engine.add_order(limit(id, &symbol, tick, 1, side)).unwrap();
+                        orders.push((id, tick, side));
+                    }
+                }
+            }
+        }
+        let mut ops = Vec::with_capacity(self.ops);
+        for _ in 0..self.ops {
+            let index = rng.below(self.symbols as u64) as usize;
+            let symbol = symbol(index);
+            let orders = &mut resting[index];
+            let side = if rng.below(2) == 0 { OrderSide::Buy } else { OrderSide::Sell };
+            let roll = rng.unit();
+            let cancel = roll < self.cancel_ratio;
+            if cancel && !orders.is_empty() {
+                let (order_id, tick, side) = orders.swap_remove(rng.below(orders.len() as u64) as usize);
+                ops.push(Op::Cancel {
+                    symbol,
+                    order_id,
+                    price: Decimal::new(tick, 2),
+                    side,
+                });
+            } else if !cancel && roll < self.cancel_ratio + self.market_ratio {
+                id += 1;
+                let volumn = self.sweep_levels * self.per_level as i64;
+                let tick = match side {
+                    OrderSide::Buy => MID + self.sweep_levels,
+                    OrderSide::Sell => MID - self.sweep_levels,
+                };
+                let order = Order::new(id, &symbol, Decimal::new(tick, 2), Decimal::new(volumn, 0), OrderKind::IOC, side, 0);
+                ops.push(Op::Add(order.with_owner(id % 100)));
+            } else {
+                id += 1;
+                // 靠近中间价的价位更活跃
+                let offset = 1 + (rng.below(self.levels as u64) * rng.below(self.levels as u64) / self.levels as u64) as i64;
+                let tick = match side {
+                    OrderSide::Buy => MID - offset,
+                    OrderSide::Sell => MID + offset,
+                };
+                ops.push(Op::Add(limit(id, &symbol, tick, 1 + rng.below(5) as i64, side)));
+                orders.push((id, tick, side));
+            }
+        }
+        return Flow { engine, ops };
+    }
+}
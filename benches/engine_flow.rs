@@ -0,0 +1,29 @@
+#![allow(clippy::needless_return)]
+//! 通过Engine回放合成订单流, 衡量整体吞吐量
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+fn engine_flow(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine_flow");
+    group.sample_size(10);
+    for config in common::FLOWS.iter() {
+        group.throughput(Throughput::Elements(config.ops as u64));
+        group.bench_function(config.name, |b| {
+            b.iter_batched(
+                || config.build(42),
+                |flow| {
+                    for op in flow.ops {
+                        op.apply(&flow.engine);
+                    }
+                    flow.engine
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, engine_flow);
+criterion_main!(benches);
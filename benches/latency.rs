@@ -0,0 +1,54 @@
+#![allow(clippy::needless_return)]
+//! 回放合成订单流, 统计吞吐量及各操作的延迟分布
+//!
+//! cargo bench --bench latency
+mod common;
+
+use std::{collections::BTreeMap, time::Instant};
+
+use hdrhistogram::Histogram;
+
+fn main() {
+    println!(
+        "{:<14}{:<8}{:>10}{:>14}{:>10}{:>10}{:>10}{:>10}",
+        "flow", "op", "count", "ops/s", "p50(ns)", "p99(ns)", "p99.9(ns)", "max(ns)"
+    );
+    for config in common::FLOWS.iter() {
+        let flow = config.build(42);
+        let mut histograms: BTreeMap<&str, Histogram<u64>> = BTreeMap::new();
+        let total = flow.ops.len();
+        let start = Instant::now();
+        for op in flow.ops {
+            let name = op.name();
+            let begin = Instant::now();
+            op.apply(&flow.engine);
+            let elapsed = begin.elapsed().as_nanos() as u64;
+            histograms
+                .entry(name)
+                .or_insert_with(|| Histogram::new(3).unwrap())
+                .record(elapsed.max(1))
+                .unwrap();
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        println!(
+            "{:<14}{:<8}{:>10}{:>14.0}",
+            config.name,
+            "all",
+            total,
+            total as f64 / elapsed
+        );
+        for (name, histogram) in histograms.iter() {
+            println!(
+                "{:<14}{:<8}{:>10}{:>14}{:>10}{:>10}{:>10}{:>10}",
+                "",
+                name,
+                histogram.len(),
+                "",
+                histogram.value_at_quantile(0.5),
+                histogram.value_at_quantile(0.99),
+                histogram.value_at_quantile(0.999),
+                histogram.max()
+            );
+        }
+    }
+}
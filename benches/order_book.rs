@@ -0,0 +1,76 @@
+#![allow(clippy::needless_return)]
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use match_trade::{Order, OrderBook, OrderKind, OrderSide};
+use rust_decimal::Decimal;
+
+const SYMBOL: &str = "BTC/USDT";
+
+fn order(id: u64, price: i64, volumn: i64, side: OrderSide) -> Order {
+    return Order::new(
+        id,
+        SYMBOL,
+        Decimal::new(price, 2),
+        Decimal::new(volumn, 0),
+        OrderKind::Limit,
+        side,
+        0,
+    );
+}
+
+/// levels档卖单, 每档per_level笔, 卖一价为100.00
+fn create_book(levels: i64, per_level: u64) -> OrderBook {
+    let mut book = OrderBook::new(SYMBOL.to_string(), None);
+    let mut id = 0;
+    for level in 0..levels {
+        for _ in 0..per_level {
+            id += 1;
+            book.add_order(order(id, 10000 + level, 1, OrderSide::Sell))
+                .unwrap();
+        }
+    }
+    return book;
+}
+
+fn add_cancel(c: &mut Criterion) {
+    let mut book = create_book(1000, 10);
+    let mut id = 1_000_000;
+    c.bench_function("add_cancel", |b| {
+        b.iter(|| {
+            id += 1;
+            book.add_order(order(id, 10500, 1, OrderSide::Sell)).unwrap();
+            book.calcen_order(id, Decimal::new(10500, 2), OrderSide::Sell)
+                .unwrap();
+        })
+    });
+}
+
+fn cancel_mid_level(c: &mut Criterion) {
+    c.bench_function("cancel_mid_level", |b| {
+        b.iter_batched(
+            || create_book(1, 1000),
+            |mut book| {
+                book.calcen_order(500, Decimal::new(10000, 2), OrderSide::Sell)
+                    .unwrap();
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn match_10_levels(c: &mut Criterion) {
+    c.bench_function("match_10_levels", |b| {
+        b.iter_batched(
+            || create_book(100, 10),
+            |mut book| {
+                book.add_order(order(1_000_000, 10009, 100, OrderSide::Buy))
+                    .unwrap();
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, add_cancel, cancel_mid_level, match_10_levels);
+criterion_main!(benches);
@@ -1,54 +1,155 @@
 use std::str::FromStr;
 
 use actix_web::{HttpResponse,  Result, web,error};
-use match_trade::{Engine,  Order, OrderKind, OrderSide};
+use match_trade::{Engine, Instrument, Order, OrderKind, OrderRef, OrderSide, Symbol, TradingStatus};
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
+use crate::BookQueues;
+
+/// 默认深度档数及成交条数
+const DEFAULT_LIMIT: usize = 20;
+
 #[derive(Deserialize)]
 pub struct TradeOrderReq {
-    order_id: u64,
-    price: String,
-    symbol: String,
-    volumn: String,
-    side: OrderSide,
-    kind: OrderKind,
-    timestamp: i64,
+    /// 为0时由Engine分配
+    #[serde(default)]
+    pub order_id: u64,
+    pub price: String,
+    pub symbol: String,
+    pub volumn: String,
+    pub side: OrderSide,
+    pub kind: OrderKind,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub owner: u64,
+    pub client_order_id: Option<String>,
+}
+
+impl TradeOrderReq {
+    pub fn to_order(&self) -> Result<Order, &'static str> {
+        // 只查找已添加的标的, 请求中的任意名称不会驻留
+        if Symbol::lookup(&self.symbol).is_none() {
+            return Err("not found symbol");
+        }
+        if let (Ok(p), Ok(v)) = (
+            Decimal::from_str(&self.price),
+            Decimal::from_str(&self.volumn),
+        ) {
+            let mut order = Order::new(
+                self.order_id,
+                &self.symbol,
+                p,
+                v,
+                self.kind,
+                self.side,
+                self.timestamp,
+            )
+            .with_owner(self.owner);
+            if let Some(client_order_id) = &self.client_order_id {
+                order = order.with_client_order_id(client_order_id);
+            }
+            return Ok(order);
+        } else {
+            return Err("invalid price or volumn");
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct CancelOrderReq {
-    order_id: u64,
-    price: String,
-    symbol: String,
-    side: OrderSide,
+    pub order_id: u64,
+    pub price: String,
+    pub symbol: String,
+    pub side: OrderSide,
+}
+
+#[derive(Deserialize)]
+pub struct AmendOrderReq {
+    pub order_id: u64,
+    pub price: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub new_price: String,
+    pub new_volumn: String,
+}
+
+impl AmendOrderReq {
+    pub fn amend(&self, engine: &Engine) -> Result<bool, &'static str> {
+        if let (Ok(p), Ok(new_p), Ok(new_v)) = (
+            Decimal::from_str(&self.price),
+            Decimal::from_str(&self.new_price),
+            Decimal::from_str(&self.new_volumn),
+        ) {
+            return engine.amend_order(&self.symbol, self.order_id, p, self.side, new_p, new_v);
+        } else {
+            return Err("invalid price or volumn");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OrderQuery {
+    pub symbol: String,
+    #[serde(default)]
+    pub order_id: u64,
+    /// 按客户订单号查询时使用
+    #[serde(default)]
+    pub owner: u64,
+    pub client_order_id: Option<String>,
+}
+
+impl OrderQuery {
+    /// 有客户订单号时按客户订单号查询
+    fn order_ref(&self) -> OrderRef {
+        return match &self.client_order_id {
+            Some(client_order_id) => OrderRef::client(self.owner, client_order_id),
+            None => OrderRef::Id(self.order_id),
+        };
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OpenOrdersQuery {
+    pub owner: u64,
+    pub symbol: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SymbolQuery {
+    pub symbol: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameBookReq {
+    pub symbol: String,
+    pub new_symbol: String,
+}
+
+#[derive(Deserialize)]
+pub struct RemoveBookReq {
+    pub symbol: String,
+}
+
+#[derive(Deserialize)]
+pub struct TradingStatusReq {
+    pub symbol: String,
+    pub status: TradingStatus,
 }
 
 pub async fn trade(
     data: web::Data<Engine>,
     info: web::Json<TradeOrderReq>,
 ) -> Result<HttpResponse> {
-    if let (Ok(p), Ok(v)) = (
-        Decimal::from_str(&info.price),
-        Decimal::from_str(&info.volumn),
-    ) {
-        let order = Order::new(
-            info.order_id,
-            &info.symbol,
-            p,
-            v,
-            info.kind.clone(),
-            info.side.clone(),
-            info.timestamp,
-        );
-        let res = data.add_order(order);
-        if let Err(e) = res{
-            return Err(error::ErrorBadRequest(e.to_string()));
-        }else{
-            return Ok(HttpResponse::Ok().body("sucess"))
+    match info.to_order() {
+        Ok(order) => {
+            match data.add_order(order) {
+                Ok(order_id) => return Ok(HttpResponse::Ok().json(order_id)),
+                Err(e) => return Err(error::ErrorBadRequest(e.to_string())),
+            }
         }
-    } else {
-        return Err(error::ErrorBadRequest("error"));
+        Err(e) => return Err(error::ErrorBadRequest(e)),
     }
 }
 
@@ -57,10 +158,120 @@ pub async fn cancel(
     info: web::Json<CancelOrderReq>,
 ) -> Result<HttpResponse> {
     if let Ok(p) = Decimal::from_str(&info.price) {
-        if let Some(order) = data.cancel_order(&info.symbol, info.order_id, p, info.side.clone()) {
+        if let Some(order) = data.cancel_order(&info.symbol, info.order_id, p, info.side) {
             return Ok(HttpResponse::Ok().json(order));
         }
     };
-    Err(error::ErrorNotFound("not found"))
+    // 区分已结束的订单与不存在的订单
+    match data.order_status(&info.symbol, &OrderRef::Id(info.order_id)) {
+        Some(record) if record.status.is_terminal() => Err(error::ErrorConflict(
+            format!("order already {:?}", record.status),
+        )),
+        _ => Err(error::ErrorNotFound("not found")),
+    }
+}
+
+pub async fn amend(
+    data: web::Data<Engine>,
+    info: web::Json<AmendOrderReq>,
+) -> Result<HttpResponse> {
+    if let Err(e) = info.amend(&data) {
+        return Err(error::ErrorBadRequest(e));
+    }
+    Ok(HttpResponse::Ok().body("sucess"))
 }
 
+pub async fn symbols(data: web::Data<Engine>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(data.symbols()))
+}
+
+/// 查询订单状态及成交明细
+pub async fn order(
+    data: web::Data<Engine>,
+    info: web::Query<OrderQuery>,
+) -> Result<HttpResponse> {
+    match data.order_status(&info.symbol, &info.order_ref()) {
+        Some(record) => Ok(HttpResponse::Ok().json(record)),
+        None => Err(error::ErrorNotFound("not found")),
+    }
+}
+
+pub async fn open_orders(
+    data: web::Data<Engine>,
+    info: web::Query<OpenOrdersQuery>,
+) -> Result<HttpResponse> {
+    let orders = data.open_orders(info.symbol.as_deref(), info.owner);
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+pub async fn depth(
+    data: web::Data<Engine>,
+    info: web::Query<SymbolQuery>,
+) -> Result<HttpResponse> {
+    let limit = info.limit.unwrap_or(DEFAULT_LIMIT);
+    match data.depth(&info.symbol, limit) {
+        Some(depth) => Ok(HttpResponse::Ok().json(depth)),
+        None => Err(error::ErrorNotFound("not found symbol")),
+    }
+}
+
+pub async fn recent_trades(
+    data: web::Data<Engine>,
+    info: web::Query<SymbolQuery>,
+) -> Result<HttpResponse> {
+    let limit = info.limit.unwrap_or(DEFAULT_LIMIT);
+    match data.recent_trades(&info.symbol, limit) {
+        Some(trades) => Ok(HttpResponse::Ok().json(trades)),
+        None => Err(error::ErrorNotFound("not found symbol")),
+    }
+}
+
+/// 新增标的 (示例未做管理员鉴权)
+pub async fn add_book(
+    data: web::Data<Engine>,
+    queues: web::Data<BookQueues>,
+    info: web::Json<Instrument>,
+) -> Result<HttpResponse> {
+    let instrument = info.into_inner();
+    let result_queue = queues.create(&instrument.symbol);
+    if let Err(e) = data.add_instrument(instrument, Some(result_queue)) {
+        return Err(error::ErrorBadRequest(e));
+    }
+    Ok(HttpResponse::Ok().body("sucess"))
+}
+
+pub async fn set_trading_status(
+    data: web::Data<Engine>,
+    info: web::Json<TradingStatusReq>,
+) -> Result<HttpResponse> {
+    if let Err(e) = data.set_trading_status(&info.symbol, info.status) {
+        return Err(error::ErrorNotFound(e));
+    }
+    Ok(HttpResponse::Ok().body("sucess"))
+}
+
+/// 下架标的, 返回被撤销的挂单
+pub async fn remove_book(
+    data: web::Data<Engine>,
+    info: web::Json<RemoveBookReq>,
+) -> Result<HttpResponse> {
+    match data.remove_book(&info.symbol) {
+        Ok(orders) => Ok(HttpResponse::Ok().json(orders)),
+        Err(e) => Err(error::ErrorNotFound(e)),
+    }
+}
+
+/// 标的改名, 结果队列随之更换
+pub async fn rename_book(
+    data: web::Data<Engine>,
+    queues: web::Data<BookQueues>,
+    info: web::Json<RenameBookReq>,
+) -> Result<HttpResponse> {
+    if let Err(e) = data.rename_book(&info.symbol, &info.new_symbol) {
+        return Err(error::ErrorBadRequest(e));
+    }
+    queues.hub.rename(&info.symbol, &info.new_symbol);
+    data.set_result_queue(&info.new_symbol, Some(queues.create(&info.new_symbol)))
+        .unwrap();
+    Ok(HttpResponse::Ok().body("sucess"))
+}
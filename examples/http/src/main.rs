@@ -1,23 +1,64 @@
 use actix_web::{App, HttpRequest, HttpServer, error, web,HttpResponse};
-use match_trade::Engine;
+use match_trade::{Engine, ResultQueue};
 mod websocket;
 mod http;
+mod market;
+mod report;
+
+/// 为标的创建执行回报 行情结果队列
+#[derive(Clone)]
+pub struct BookQueues {
+    pub router: report::ExecRouter,
+    pub hub: market::MarketHub,
+}
+
+impl BookQueues {
+    pub fn create(&self, symbol: &str) -> Box<dyn ResultQueue + Send + Sync> {
+        let queues: Vec<Box<dyn ResultQueue + Send + Sync>> = vec![
+            Box::new(self.router.book_queue(symbol)),
+            Box::new(self.hub.book_queue(symbol)),
+        ];
+        Box::new(queues)
+    }
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let mut engine: Engine = Engine::new();
+    let queues = BookQueues {
+        router: report::ExecRouter::default(),
+        hub: market::MarketHub::default(),
+    };
+    let engine: Engine = Engine::new();
     {
-        engine.add_book("BTC/DOGE", None);
+        engine.add_book("BTC/DOGE", Some(queues.create("BTC/DOGE"))).unwrap();
     }
     let app_data = web::Data::new(engine);
+    let router_data = web::Data::new(queues.router.clone());
+    let hub_data = web::Data::new(queues.hub.clone());
+    let queues_data = web::Data::new(queues);
 
     HttpServer::new(move || {
         // move counter into the closure
         App::new()
         .app_data(web::JsonConfig::default().limit(4096).error_handler(json_error_handler))
             .app_data(app_data.clone())
+            .app_data(router_data.clone())
+            .app_data(hub_data.clone())
+            .app_data(queues_data.clone())
             .route("/trade",web::to(http::trade))
             .route("/cancel",web::to(http::cancel))
+            .route("/amend",web::to(http::amend))
+            .route("/symbols",web::get().to(http::symbols))
+            .route("/order",web::get().to(http::order))
+            .route("/orders",web::get().to(http::open_orders))
+            .route("/depth",web::get().to(http::depth))
+            .route("/trades",web::get().to(http::recent_trades))
+            .route("/admin/book",web::post().to(http::add_book))
+            .route("/admin/status",web::post().to(http::set_trading_status))
+            .route("/admin/delist",web::post().to(http::remove_book))
+            .route("/admin/rename",web::post().to(http::rename_book))
+            .route("/ws",web::get().to(websocket::index))
+            .route("/market",web::get().to(market::index))
     })
     .bind("127.0.0.1:8080")?
     .run()
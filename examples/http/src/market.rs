@@ -0,0 +1,389 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix::{
+    prelude::SendError, Actor, ActorContext, AsyncContext, Handler, Message, Recipient,
+    StreamHandler,
+};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use match_trade::{Order, OrderSide, ResultQueue, Trade, TradeRecord};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// 心跳检测间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// 超过该时间未收到客户端消息视为断线
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 连接待发送消息上限, 超出后视为慢消费者
+const MAILBOX_CAPACITY: usize = 1024;
+/// K线周期
+const KLINE_INTERVAL: i64 = 60_000;
+/// 保留24小时K线用于计算ticker
+const TICKER_WINDOW: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Trade,
+    Depth,
+    Ticker,
+    Kline,
+}
+
+/// 推送给订阅者的行情消息, 序列化一次后共享
+#[derive(Clone, Message)]
+#[rtype(result = "()")]
+pub struct MarketMsg(pub Arc<String>);
+
+#[derive(Clone, Serialize)]
+struct Kline {
+    open_time: i64,
+    open: Decimal,
+    high: Decimal,
+    low: Decimal,
+    close: Decimal,
+    volumn: Decimal,
+}
+
+#[derive(Default)]
+struct SymbolState {
+    /// 深度增量序号
+    seq: u64,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    /// 24小时内的K线, 按开盘时间淘汰
+    klines: VecDeque<Kline>,
+    /// 窗口内K线成交量之和
+    volumn: Decimal,
+    /// 窗口内最高价候选 (open_time, 价格), 价格递减
+    highs: VecDeque<(i64, Decimal)>,
+    /// 窗口内最低价候选 (open_time, 价格), 价格递增
+    lows: VecDeque<(i64, Decimal)>,
+    subscribers: HashMap<Channel, HashMap<usize, Recipient<MarketMsg>>>,
+}
+
+impl SymbolState {
+    fn snapshot(&self, symbol: &str, channel: Channel) -> Option<serde_json::Value> {
+        match channel {
+            Channel::Depth => Some(json!({
+                "channel": channel,
+                "symbol": symbol,
+                "type": "snapshot",
+                "seq": self.seq,
+                "bids": self.bids.iter().rev().collect::<Vec<_>>(),
+                "asks": self.asks.iter().collect::<Vec<_>>(),
+            })),
+            Channel::Ticker => self.ticker(symbol),
+            Channel::Kline => self.klines.back().map(|v| {
+                json!({"channel": channel, "symbol": symbol, "data": v})
+            }),
+            Channel::Trade => None,
+        }
+    }
+
+    /// 由滚动统计生成, 不遍历K线
+    fn ticker(&self, symbol: &str) -> Option<serde_json::Value> {
+        let last = self.klines.back()?;
+        let first = self.klines.front()?;
+        Some(json!({
+            "channel": Channel::Ticker,
+            "symbol": symbol,
+            "last": last.close,
+            "open": first.open,
+            "high": self.highs.front()?.1,
+            "low": self.lows.front()?.1,
+            "volumn": self.volumn,
+            "best_bid": self.bids.keys().next_back(),
+            "best_ask": self.asks.keys().next(),
+        }))
+    }
+
+    /// 淘汰开盘时间在24小时前的K线, 同步扣减滚动统计
+    fn evict(&mut self, now: i64) {
+        let cutoff = now - now % KLINE_INTERVAL - TICKER_WINDOW;
+        while self.klines.front().is_some_and(|v| v.open_time <= cutoff) {
+            if let Some(kline) = self.klines.pop_front() {
+                self.volumn -= kline.volumn;
+            }
+        }
+        while self.highs.front().is_some_and(|v| v.0 <= cutoff) {
+            self.highs.pop_front();
+        }
+        while self.lows.front().is_some_and(|v| v.0 <= cutoff) {
+            self.lows.pop_front();
+        }
+    }
+
+    fn on_trade(&mut self, price: Decimal, volumn: Decimal, now: i64) {
+        self.evict(now);
+        let open_time = now - now % KLINE_INTERVAL;
+        self.volumn += volumn;
+        // 同一K线已有更优的候选时不再加入, 候选数不超过K线数
+        while self.highs.back().is_some_and(|v| v.1 <= price) {
+            self.highs.pop_back();
+        }
+        if self.highs.back().is_none_or(|v| v.0 != open_time) {
+            self.highs.push_back((open_time, price));
+        }
+        while self.lows.back().is_some_and(|v| v.1 >= price) {
+            self.lows.pop_back();
+        }
+        if self.lows.back().is_none_or(|v| v.0 != open_time) {
+            self.lows.push_back((open_time, price));
+        }
+        match self.klines.back_mut() {
+            Some(kline) if kline.open_time == open_time => {
+                kline.high = kline.high.max(price);
+                kline.low = kline.low.min(price);
+                kline.close = price;
+                kline.volumn += volumn;
+            }
+            _ => {
+                self.klines.push_back(Kline {
+                    open_time,
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    volumn,
+                });
+            }
+        }
+    }
+
+    /// 非阻塞推送, 邮箱已满的订阅者被移除并收到lagged通知, 需重新订阅
+    fn publish(&mut self, symbol: &str, channel: Channel, msg: serde_json::Value) {
+        if let Some(subscribers) = self.subscribers.get_mut(&channel) {
+            let msg = MarketMsg(Arc::new(msg.to_string()));
+            subscribers.retain(|_, recipient| match recipient.try_send(msg.clone()) {
+                Ok(_) => true,
+                Err(SendError::Full(_)) => {
+                    let lagged = json!({"channel": channel, "symbol": symbol, "type": "lagged"});
+                    let _ = recipient.do_send(MarketMsg(Arc::new(lagged.to_string())));
+                    false
+                }
+                Err(SendError::Closed(_)) => false,
+            });
+        }
+    }
+}
+
+/// 行情广播中心
+///
+/// 每个标的的行情状态单独加锁, 成交及深度推送只锁定所属标的
+#[derive(Clone, Default)]
+pub struct MarketHub {
+    symbols: Arc<Mutex<HashMap<String, Arc<Mutex<SymbolState>>>>>,
+}
+
+impl MarketHub {
+    fn state(&self, symbol: &str) -> Arc<Mutex<SymbolState>> {
+        let mut symbols = self.symbols.lock().unwrap();
+        symbols.entry(symbol.to_string()).or_default().clone()
+    }
+
+    /// 创建标的对应的结果队列
+    pub fn book_queue(&self, symbol: &str) -> BookMarketQueue {
+        BookMarketQueue {
+            symbol: symbol.to_string(),
+            state: self.state(symbol),
+        }
+    }
+
+    /// 订阅, 先推送快照, 之后推送增量
+    pub fn subscribe(
+        &self,
+        symbol: &str,
+        channel: Channel,
+        session_id: usize,
+        recipient: Recipient<MarketMsg>,
+    ) {
+        let state = self.state(symbol);
+        let mut state = state.lock().unwrap();
+        // 长时间没有成交时ticker快照不含24小时前的K线
+        state.evict(now());
+        if let Some(snapshot) = state.snapshot(symbol, channel) {
+            let _ = recipient.do_send(MarketMsg(Arc::new(snapshot.to_string())));
+        }
+        state
+            .subscribers
+            .entry(channel)
+            .or_default()
+            .insert(session_id, recipient);
+    }
+
+    pub fn unsubscribe(&self, symbol: &str, channel: Channel, session_id: usize) {
+        let state = self.symbols.lock().unwrap().get(symbol).cloned();
+        if let Some(state) = state {
+            if let Some(subscribers) = state.lock().unwrap().subscribers.get_mut(&channel) {
+                subscribers.remove(&session_id);
+            }
+        }
+    }
+
+    /// 标的改名, 迁移行情状态及订阅
+    pub fn rename(&self, symbol: &str, new_symbol: &str) {
+        let mut symbols = self.symbols.lock().unwrap();
+        if let Some(state) = symbols.remove(symbol) {
+            symbols.insert(new_symbol.to_string(), state);
+        }
+    }
+
+    pub fn unsubscribe_all(&self, session_id: usize) {
+        let states: Vec<_> = self.symbols.lock().unwrap().values().cloned().collect();
+        for state in states {
+            for subscribers in state.lock().unwrap().subscribers.values_mut() {
+                subscribers.remove(&session_id);
+            }
+        }
+    }
+}
+
+/// 当前时间, 毫秒
+fn now() -> i64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |v| v.as_millis() as i64);
+}
+
+pub struct BookMarketQueue {
+    symbol: String,
+    state: Arc<Mutex<SymbolState>>,
+}
+
+impl ResultQueue for BookMarketQueue {
+    fn trade_success(&self, trade: &Trade) {
+        let mut state = self.state.lock().unwrap();
+        state.on_trade(trade.price, trade.volumn, trade.timestamp);
+        let msg = json!({
+            "channel": Channel::Trade,
+            "symbol": self.symbol,
+            "data": TradeRecord::from(trade),
+        });
+        state.publish(&self.symbol, Channel::Trade, msg);
+        if let Some(msg) = state.snapshot(&self.symbol, Channel::Kline) {
+            state.publish(&self.symbol, Channel::Kline, msg);
+        }
+        if let Some(msg) = state.ticker(&self.symbol) {
+            state.publish(&self.symbol, Channel::Ticker, msg);
+        }
+    }
+
+    fn cancel_order(&self, _order: &Order) {}
+
+    fn depth_changed(&self, side: OrderSide, price: Decimal, volumn: Decimal) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let levels = match side {
+            OrderSide::Buy => &mut state.bids,
+            OrderSide::Sell => &mut state.asks,
+        };
+        let level = levels.entry(price).or_default();
+        *level += volumn;
+        let level = *level;
+        if level.is_zero() {
+            levels.remove(&price);
+        }
+        state.seq += 1;
+        let msg = json!({
+            "channel": Channel::Depth,
+            "symbol": self.symbol,
+            "type": "update",
+            "seq": state.seq,
+            "side": side,
+            "price": price,
+            "volumn": level,
+        });
+        state.publish(&self.symbol, Channel::Depth, msg);
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum MarketReq {
+    Subscribe { symbol: String, channel: Channel },
+    Unsubscribe { symbol: String, channel: Channel },
+    Ping,
+}
+
+/// 公共行情连接
+pub struct MarketWs {
+    id: usize,
+    hub: web::Data<MarketHub>,
+    hb: Instant,
+}
+
+impl Actor for MarketWs {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.set_mailbox_capacity(MAILBOX_CAPACITY);
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.hub.unsubscribe_all(self.id);
+    }
+}
+
+impl Handler<MarketMsg> for MarketWs {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketMsg, ctx: &mut Self::Context) {
+        ctx.text(msg.0.as_str());
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for MarketWs {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        self.hb = Instant::now();
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Text(text)) => match serde_json::from_str::<MarketReq>(&text) {
+                Ok(MarketReq::Subscribe { symbol, channel }) => {
+                    self.hub
+                        .subscribe(&symbol, channel, self.id, ctx.address().recipient());
+                }
+                Ok(MarketReq::Unsubscribe { symbol, channel }) => {
+                    self.hub.unsubscribe(&symbol, channel, self.id);
+                }
+                Ok(MarketReq::Ping) => ctx.text(json!({"op": "pong"}).to_string()),
+                Err(e) => ctx.text(json!({"op": "error", "msg": e.to_string()}).to_string()),
+            },
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(_) => ctx.stop(),
+            _ => (),
+        }
+    }
+}
+
+pub async fn index(
+    req: HttpRequest,
+    stream: web::Payload,
+    hub: web::Data<MarketHub>,
+) -> Result<HttpResponse, Error> {
+    let id = crate::websocket::next_session_id();
+    ws::start(
+        MarketWs {
+            id,
+            hub,
+            hb: Instant::now(),
+        },
+        &req,
+        stream,
+    )
+}
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use actix::{Message, Recipient};
+use match_trade::{Order, OrderSide, ResultQueue, Trade};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+/// 执行回报
+#[derive(Clone, Message, Serialize)]
+#[rtype(result = "()")]
+pub struct ExecReport {
+    /// ack fill cancel reject amend
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub order_id: u64,
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub price: Decimal,
+    pub volumn: Decimal,
+    pub fee: Option<Decimal>,
+    pub reason: Option<&'static str>,
+}
+
+impl ExecReport {
+    fn from_order(kind: &'static str, symbol: &str, order: &Order) -> Self {
+        ExecReport {
+            kind,
+            order_id: order.id,
+            client_order_id: order.client_order_id.clone(),
+            symbol: symbol.to_string(),
+            side: order.side,
+            price: order.price,
+            volumn: order.volumn,
+            fee: None,
+            reason: None,
+        }
+    }
+}
+
+/// 按账户将执行回报推送到对应的连接
+#[derive(Clone, Default)]
+pub struct ExecRouter {
+    sessions: Arc<RwLock<HashMap<u64, HashMap<usize, Recipient<ExecReport>>>>>,
+}
+
+impl ExecRouter {
+    pub fn register(&self, owner: u64, session_id: usize, recipient: Recipient<ExecReport>) {
+        self.sessions
+            .write()
+            .unwrap()
+            .entry(owner)
+            .or_default()
+            .insert(session_id, recipient);
+    }
+
+    pub fn unregister(&self, owner: u64, session_id: usize) {
+        let mut sessions = self.sessions.write().unwrap();
+        if let Some(v) = sessions.get_mut(&owner) {
+            v.remove(&session_id);
+            if v.is_empty() {
+                sessions.remove(&owner);
+            }
+        }
+    }
+
+    /// 创建标的对应的结果队列
+    pub fn book_queue(&self, symbol: &str) -> BookExecQueue {
+        BookExecQueue {
+            symbol: symbol.to_string(),
+            router: self.clone(),
+        }
+    }
+
+    fn send(&self, owner: u64, report: ExecReport) {
+        if let Some(v) = self.sessions.read().unwrap().get(&owner) {
+            for recipient in v.values() {
+                // 连接已断开时丢弃回报
+                let _ = recipient.do_send(report.clone());
+            }
+        }
+    }
+}
+
+pub struct BookExecQueue {
+    symbol: String,
+    router: ExecRouter,
+}
+
+impl ResultQueue for BookExecQueue {
+    fn order_accepted(&self, order: &Order) {
+        self.router
+            .send(order.owner, ExecReport::from_order("ack", &self.symbol, order));
+    }
+
+    fn order_rejected(&self, order: &Order, reason: &'static str) {
+        let mut report = ExecReport::from_order("reject", &self.symbol, order);
+        report.reason = Some(reason);
+        self.router.send(order.owner, report);
+    }
+
+    fn trade_success(&self, trade: &Trade) {
+        let maker_side = match trade.taker_side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        for (owner, order_id, client_order_id, side, fee) in [
+            (trade.maker_owner, trade.maker_id, &trade.maker_client_order_id, maker_side, trade.maker_fee),
+            (trade.taker_owner, trade.taker_id, &trade.taker_client_order_id, trade.taker_side, trade.taker_fee),
+        ] {
+            self.router.send(
+                owner,
+                ExecReport {
+                    kind: "fill",
+                    order_id,
+                    client_order_id: client_order_id.clone(),
+                    symbol: self.symbol.clone(),
+                    side,
+                    price: trade.price,
+                    volumn: trade.volumn,
+                    fee: Some(fee),
+                    reason: None,
+                },
+            );
+        }
+    }
+
+    fn cancel_order(&self, order: &Order) {
+        self.router
+            .send(order.owner, ExecReport::from_order("cancel", &self.symbol, order));
+    }
+
+    fn order_amended(&self, order: &Order) {
+        self.router
+            .send(order.owner, ExecReport::from_order("amend", &self.symbol, order));
+    }
+}
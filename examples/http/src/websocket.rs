@@ -1,29 +1,297 @@
-use actix::{Actor, StreamHandler};
-use actix_web::web::Bytes;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use actix::{Actor, ActorContext, AsyncContext, Handler, StreamHandler};
+use actix_web::{web, web::Bytes, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
-struct Ws;
+use match_trade::{Engine, Order, OrderRef};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    http::{AmendOrderReq, CancelOrderReq, TradeOrderReq},
+    report::{ExecReport, ExecRouter},
+};
+
+/// 心跳检测间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// 超过该时间未收到客户端消息视为断线
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static SESSION_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// 分配连接编号
+pub fn next_session_id() -> usize {
+    SESSION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WsReq {
+    Login {
+        owner: u64,
+    },
+    Ping,
+    Trade {
+        #[serde(flatten)]
+        order: TradeOrderReq,
+        /// 断线时自动撤销
+        #[serde(default)]
+        cancel_on_disconnect: bool,
+    },
+    Cancel(CancelOrderReq),
+    Amend(AmendOrderReq),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WsResp {
+    Login {
+        owner: u64,
+    },
+    Pong,
+    Trade {
+        order_id: u64,
+        client_order_id: Option<String>,
+    },
+    Cancel {
+        order: Order,
+        client_order_id: Option<String>,
+    },
+    Amend {
+        order_id: u64,
+        client_order_id: Option<String>,
+    },
+    Report(ExecReport),
+    Error {
+        msg: String,
+    },
+}
+
+/// 会话内提交的订单
+struct SessionOrder {
+    client_order_id: Option<String>,
+    cancel_on_disconnect: bool,
+}
+
+pub struct Ws {
+    id: usize,
+    engine: web::Data<Engine>,
+    router: web::Data<ExecRouter>,
+    owner: Option<u64>,
+    /// (symbol, order_id) -> 订单
+    orders: HashMap<(String, u64), SessionOrder>,
+    hb: Instant,
+}
 
 impl Actor for Ws {
     type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        if let Some(owner) = self.owner {
+            self.router.unregister(owner, self.id);
+        }
+        self.cancel_on_disconnect();
+    }
 }
 
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Ws {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        self.hb = Instant::now();
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
-            Ok(ws::Message::Text(text)) => ctx.text(self.text(text)),
-            Ok(ws::Message::Binary(bin)) => ctx.binary(self.binary(bin)),
+            Ok(ws::Message::Text(text)) => {
+                let resp = self.text(text, ctx);
+                ctx.text(resp);
+            }
+            Ok(ws::Message::Binary(bin)) => {
+                let resp = self.binary(bin, ctx);
+                ctx.binary(resp);
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(_) => ctx.stop(),
             _ => (),
         }
     }
 }
 
+impl Handler<ExecReport> for Ws {
+    type Result = ();
+
+    fn handle(&mut self, report: ExecReport, ctx: &mut Self::Context) {
+        let finished = match report.kind {
+            "cancel" | "reject" => true,
+            // 全部成交后不再跟踪
+            "fill" => self
+                .engine
+                .order_status(&report.symbol, &OrderRef::Id(report.order_id))
+                .map_or(true, |v| v.status.is_terminal()),
+            _ => false,
+        };
+        if finished {
+            self.orders.remove(&(report.symbol.clone(), report.order_id));
+        }
+        ctx.text(serde_json::to_string(&WsResp::Report(report)).unwrap());
+    }
+}
+
 impl Ws {
-    fn text(&self,msg: String) -> String {
-        "".to_string()
+    pub fn new(engine: web::Data<Engine>, router: web::Data<ExecRouter>) -> Self {
+        Ws {
+            id: next_session_id(),
+            engine,
+            router,
+            owner: None,
+            orders: HashMap::new(),
+            hb: Instant::now(),
+        }
     }
 
-    fn binary(&self,msg:Bytes)->Bytes {
-        Bytes::from("")
+    fn text(&mut self, msg: String, ctx: &mut ws::WebsocketContext<Self>) -> String {
+        let resp = match serde_json::from_str::<WsReq>(&msg) {
+            Ok(req) => self.handle_req(req, ctx),
+            Err(e) => WsResp::Error { msg: e.to_string() },
+        };
+        serde_json::to_string(&resp).unwrap()
     }
-}
\ No newline at end of file
+
+    fn binary(&mut self, msg: Bytes, ctx: &mut ws::WebsocketContext<Self>) -> Bytes {
+        Bytes::from(self.text(String::from_utf8_lossy(&msg).to_string(), ctx))
+    }
+
+    fn handle_req(&mut self, req: WsReq, ctx: &mut ws::WebsocketContext<Self>) -> WsResp {
+        if let WsReq::Login { owner } = req {
+            if let Some(old) = self.owner.replace(owner) {
+                self.router.unregister(old, self.id);
+            }
+            self.router
+                .register(owner, self.id, ctx.address().recipient());
+            return WsResp::Login { owner };
+        }
+        if let WsReq::Ping = req {
+            return WsResp::Pong;
+        }
+        let owner = match self.owner {
+            Some(owner) => owner,
+            None => return error("not logged in"),
+        };
+        match req {
+            WsReq::Trade {
+                mut order,
+                cancel_on_disconnect,
+            } => {
+                order.owner = owner;
+                let client_order_id = order.client_order_id.clone();
+                let order = match order.to_order() {
+                    Ok(order) => order,
+                    Err(e) => return error(e),
+                };
+                let symbol = order.symbol.to_string();
+                match self.engine.add_order(order) {
+                    Ok(order_id) => {
+                        // 撮合过程中的回报经邮箱投递, 在本次处理结束后才到达, 此时已按分配的订单号记录
+                        self.orders.insert(
+                            (symbol, order_id),
+                            SessionOrder {
+                                client_order_id: client_order_id.clone(),
+                                cancel_on_disconnect,
+                            },
+                        );
+                        WsResp::Trade {
+                            order_id,
+                            client_order_id,
+                        }
+                    }
+                    Err(e) => error(e),
+                }
+            }
+            WsReq::Cancel(req) => {
+                // 只能撤销本会话提交且属于当前账户的订单
+                let price = match Decimal::from_str(&req.price) {
+                    Ok(price) => price,
+                    Err(_) => return error("invalid price"),
+                };
+                let client_order_id = match self.session_order(owner, &req.symbol, req.order_id) {
+                    Some(client_order_id) => client_order_id,
+                    None => return error("not found"),
+                };
+                match self
+                    .engine
+                    .cancel_order(&req.symbol, req.order_id, price, req.side)
+                {
+                    Some(order) => WsResp::Cancel {
+                        order,
+                        client_order_id,
+                    },
+                    None => error("not found"),
+                }
+            }
+            WsReq::Amend(req) => {
+                let client_order_id = match self.session_order(owner, &req.symbol, req.order_id) {
+                    Some(client_order_id) => client_order_id,
+                    None => return error("not found"),
+                };
+                if let Err(e) = req.amend(&self.engine) {
+                    return error(e);
+                }
+                WsResp::Amend {
+                    order_id: req.order_id,
+                    client_order_id,
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// 本会话提交且仍属于该账户的订单, 返回其客户订单号
+    fn session_order(&self, owner: u64, symbol: &str, order_id: u64) -> Option<Option<String>> {
+        let order = self.orders.get(&(symbol.to_string(), order_id))?;
+        self.engine
+            .get_order(symbol, order_id)
+            .filter(|v| v.owner == owner)?;
+        Some(order.client_order_id.clone())
+    }
+
+    /// 撤销标记为断线撤单的订单
+    ///
+    /// 按订单号在订单簿中查找当前价格, 市价单和改过价的订单也能撤销
+    fn cancel_on_disconnect(&mut self) {
+        for ((symbol, order_id), order) in self.orders.drain() {
+            if order.cancel_on_disconnect {
+                self.engine.cancel_by_ref(&symbol, &OrderRef::Id(order_id));
+            }
+        }
+    }
+}
+
+fn error(msg: &str) -> WsResp {
+    WsResp::Error {
+        msg: msg.to_string(),
+    }
+}
+
+pub async fn index(
+    req: HttpRequest,
+    stream: web::Payload,
+    engine: web::Data<Engine>,
+    router: web::Data<ExecRouter>,
+) -> Result<HttpResponse, Error> {
+    ws::start(Ws::new(engine, router), &req, stream)
+}
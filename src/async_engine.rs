@@ -0,0 +1,304 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    channel::{mpsc, oneshot},
+    Stream,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    sequencer::run, CancelFilter, Depth, Instrument, LocalEngine, Order, OrderSide, ResultQueue,
+    Sequencer, Trade,
+};
+
+/// 撮合事件
+#[derive(Clone, Debug)]
+pub enum ExecEvent {
+    Accepted(Order),
+    Rejected(Order, &'static str),
+    Trade {
+        symbol: String,
+        trade: Trade,
+    },
+    /// volumn为撤销数量
+    Cancelled(Order),
+    Amended(Order),
+    /// volumn为价位数量变化
+    Depth {
+        symbol: String,
+        side: OrderSide,
+        price: Decimal,
+        volumn: Decimal,
+    },
+}
+
+/// 命令执行回报, 新建订单时result为订单号
+#[derive(Clone, Debug)]
+pub struct ExecReport<T = bool> {
+    pub result: Result<T, &'static str>,
+    /// 该命令产生的事件
+    pub events: Vec<ExecEvent>,
+}
+
+impl<T> ExecReport<T> {
+    fn error(reason: &'static str) -> ExecReport<T> {
+        return ExecReport {
+            result: Err(reason),
+            events: Vec::new(),
+        };
+    }
+}
+
+#[derive(Default)]
+struct EventHub {
+    subscribers: Mutex<Vec<mpsc::Sender<ExecEvent>>>,
+    /// 正在执行的命令产生的事件, 只在撮合线程写入
+    report: Mutex<Option<Vec<ExecEvent>>>,
+}
+
+impl EventHub {
+    fn publish(&self, event: ExecEvent) {
+        if let Some(events) = self.report.lock().unwrap().as_mut() {
+            events.push(event.clone());
+        }
+        // 不等待订阅者, 队列已满的订阅者被断开
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain_mut(|v| v.try_send(event.clone()).is_ok());
+    }
+}
+
+/// 将标的事件转发到EventHub
+struct EventQueue {
+    symbol: String,
+    hub: Arc<EventHub>,
+}
+
+impl ResultQueue for EventQueue {
+    fn order_accepted(&self, order: &Order) {
+        self.hub.publish(ExecEvent::Accepted(order.clone()));
+    }
+
+    fn order_rejected(&self, order: &Order, reason: &'static str) {
+        self.hub.publish(ExecEvent::Rejected(order.clone(), reason));
+    }
+
+    fn trade_success(&self, trade: &Trade) {
+        self.hub.publish(ExecEvent::Trade {
+            symbol: self.symbol.clone(),
+            trade: trade.clone(),
+        });
+    }
+
+    fn cancel_order(&self, order: &Order) {
+        self.hub.publish(ExecEvent::Cancelled(order.clone()));
+    }
+
+    fn order_amended(&self, order: &Order) {
+        self.hub.publish(ExecEvent::Amended(order.clone()));
+    }
+
+    fn depth_changed(&self, side: OrderSide, price: Decimal, volumn: Decimal) {
+        self.hub.publish(ExecEvent::Depth {
+            symbol: self.symbol.clone(),
+            side,
+            price,
+            volumn,
+        });
+    }
+}
+
+fn event_queue(
+    hub: &Arc<EventHub>,
+    symbol: &str,
+    result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+) -> Box<dyn ResultQueue + Send + Sync> {
+    let mut queues: Vec<Box<dyn ResultQueue + Send + Sync>> = vec![Box::new(EventQueue {
+        symbol: symbol.to_string(),
+        hub: hub.clone(),
+    })];
+    queues.extend(result_queue);
+    return Box::new(queues);
+}
+
+/// 异步Engine
+///
+/// 命令非阻塞地投递到撮合线程, 队列满时直接返回"sequencer full", 不阻塞调用方的reactor.
+/// 投递在调用时完成, 返回的future可以晚些再await, 执行顺序即调用顺序
+pub struct AsyncEngine {
+    sequencer: Sequencer,
+    hub: Arc<EventHub>,
+    /// 每个事件订阅者可缓存的事件数
+    capacity: usize,
+}
+
+impl AsyncEngine {
+    /// # Examples
+    ///
+    /// ```
+    /// use match_trade::*;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    /// futures::executor::block_on(async {
+    ///     let engine = AsyncEngine::new(LocalEngine::new(), 1024);
+    ///     engine.add_book("DOGE/BTC", None).await.unwrap();
+    ///     let order = Order::new(1,"DOGE/BTC",Decimal::from_str("1").unwrap(),Decimal::from_str("1").unwrap(),OrderKind::Limit,OrderSide::Buy,1000000000);
+    ///     let report = engine.add_order(order.clone()).await;
+    ///     assert!(report.result==Ok(1));
+    ///     engine.cancel_order("DOGE/BTC",order.id,order.price,order.side).await.result.unwrap();
+    /// });
+    /// ```
+    pub fn new(mut engine: LocalEngine, capacity: usize) -> AsyncEngine {
+        let hub = Arc::new(EventHub::default());
+        // 已有标的的结果队列同样接入事件流
+        for instrument in engine.symbols() {
+            if let Some(book) = engine.book_mut(&instrument.symbol) {
+                let result_queue = book.result_queue.take();
+                book.result_queue = Some(event_queue(&hub, &instrument.symbol, result_queue));
+            }
+        }
+        return AsyncEngine {
+            sequencer: Sequencer::new(engine, capacity),
+            hub,
+            capacity,
+        };
+    }
+
+    /// 订阅所有标的的事件, 消费过慢时流结束, 需重新订阅
+    pub fn events(&self) -> impl Stream<Item = ExecEvent> + Send + Unpin {
+        let (sender, receiver) = mpsc::channel(self.capacity);
+        self.hub.subscribers.lock().unwrap().push(sender);
+        return receiver;
+    }
+
+    /// 在撮合线程执行
+    pub fn execute<F, T>(&self, func: F) -> impl Future<Output = Result<T, &'static str>> + Send
+    where
+        F: FnOnce(&mut LocalEngine) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let sent = self.sequencer.try_send(Box::new(move |engine: &mut LocalEngine| {
+            let _ = sender.send(run(engine, func));
+        }));
+        return async move {
+            sent?;
+            return receiver.await.map_err(|_| "sequencer stopped")?;
+        };
+    }
+
+    /// 执行命令并收集其产生的事件
+    fn report<F, T>(&self, func: F) -> impl Future<Output = ExecReport<T>> + Send
+    where
+        F: FnOnce(&mut LocalEngine) -> Result<T, &'static str> + Send + 'static,
+        T: Send + 'static,
+    {
+        let hub = self.hub.clone();
+        let report = self.execute(move |engine| {
+            *hub.report.lock().unwrap() = Some(Vec::new());
+            let result = func(engine);
+            let events = hub.report.lock().unwrap().take().unwrap_or_default();
+            return ExecReport { result, events };
+        });
+        return async move { report.await.unwrap_or_else(ExecReport::error) };
+    }
+
+    /// 添加标的, 事件同时推送到result_queue
+    pub fn add_book(
+        &self,
+        symbol: &str,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> impl Future<Output = Result<bool, &'static str>> + Send {
+        return self.add_instrument(Instrument::new(symbol), result_queue);
+    }
+
+    /// 按标的配置添加标的
+    pub fn add_instrument(
+        &self,
+        instrument: Instrument,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> impl Future<Output = Result<bool, &'static str>> + Send {
+        let hub = self.hub.clone();
+        let result = self.execute(move |engine| {
+            let result_queue = event_queue(&hub, &instrument.symbol, result_queue);
+            return engine.add_instrument(instrument, Some(result_queue));
+        });
+        return async move { result.await.and_then(|v| v) };
+    }
+
+    /// 下架标的, 撤销所有挂单并返回
+    pub fn remove_book(&self, symbol: &str) -> impl Future<Output = Result<Vec<Order>, &'static str>> + Send {
+        let symbol = symbol.to_string();
+        let result = self.execute(move |engine| engine.remove_book(&symbol));
+        return async move { result.await.and_then(|v| v) };
+    }
+
+    /// 新建order, 回报中返回订单号
+    pub fn add_order(&self, order: Order) -> impl Future<Output = ExecReport<u64>> + Send {
+        return self.report(move |engine| engine.add_order(order));
+    }
+
+    /// 撤销order
+    pub fn cancel_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+    ) -> impl Future<Output = ExecReport> + Send {
+        let symbol = symbol.to_string();
+        return self.report(move |engine| {
+            match engine.cancel_order(&symbol, order_id, price, side) {
+                Some(_) => Ok(true),
+                None => Err("not found order"),
+            }
+        });
+    }
+
+    /// 修改order
+    pub fn amend_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> impl Future<Output = ExecReport> + Send {
+        let symbol = symbol.to_string();
+        return self.report(move |engine| {
+            engine.amend_order(&symbol, order_id, price, side, new_price, new_volumn)
+        });
+    }
+
+    /// 批量撤单, 返回被撤销的订单
+    pub fn mass_cancel(&self, filter: CancelFilter) -> impl Future<Output = Vec<Order>> + Send {
+        let result = self.execute(move |engine| engine.mass_cancel(&filter));
+        return async move { result.await.unwrap_or_default() };
+    }
+
+    /// 查看盘口深度
+    pub fn depth(&self, symbol: &str, limit: usize) -> impl Future<Output = Option<Depth>> + Send {
+        let symbol = symbol.to_string();
+        let result = self.execute(move |engine| engine.depth(&symbol, limit));
+        return async move { result.await.ok().flatten() };
+    }
+
+    /// 查看挂单
+    pub fn get_order(&self, symbol: &str, order_id: u64) -> impl Future<Output = Option<Order>> + Send {
+        let symbol = symbol.to_string();
+        let result = self.execute(move |engine| engine.get_order(&symbol, order_id));
+        return async move { result.await.ok().flatten() };
+    }
+
+    /// 查看账户挂单, symbol为None时查询所有标的
+    pub fn open_orders(&self, symbol: Option<&str>, owner: u64) -> impl Future<Output = Vec<Order>> + Send {
+        let symbol = symbol.map(|v| v.to_string());
+        let result = self.execute(move |engine| engine.open_orders(symbol.as_deref(), owner));
+        return async move { result.await.unwrap_or_default() };
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    sync::atomic::{AtomicI64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// 时钟, 引擎的受理时间 成交时间及风控计时均由此获取
+pub trait Clock: Send + Sync {
+    /// 当前时间, 毫秒
+    fn now(&self) -> i64;
+}
+
+/// 系统时钟
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        return SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |v| v.as_millis() as i64);
+    }
+}
+
+/// 手动设置的时钟, 用于测试及回放
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicI64,
+}
+
+impl ManualClock {
+    pub fn new(now: i64) -> ManualClock {
+        ManualClock {
+            now: AtomicI64::new(now),
+        }
+    }
+
+    pub fn set(&self, now: i64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    /// 向前推进millis毫秒
+    pub fn advance(&self, millis: i64) {
+        self.now.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> i64 {
+        return self.now.load(Ordering::SeqCst);
+    }
+}
@@ -1,12 +1,33 @@
-use std::{collections::HashMap, sync::RwLock};
+use std::{collections::HashMap, sync::{Arc, Mutex, RwLock}};
 
 use rust_decimal::Decimal;
+use serde::Deserialize;
 
-use crate::OrderSide;
+use crate::{local_engine::{self, group_symbol, Accounts}, Clock, Depth, Exposure, FeeSchedule, Instrument, OrderGroup, OrderIds, OrderRecord, OrderRef, OrderSide, RiskLimits, SystemClock, TradeRecord, TradingStatus};
 
 use super::{queue::ResultQueue, Order, OrderBook};
+
+/// 批量撤单条件, None为不限制
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct CancelFilter {
+    pub owner: Option<u64>,
+    pub symbol: Option<String>,
+    pub side: Option<OrderSide>,
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+}
+
+/// 多线程Engine
+///
+/// LocalEngine的加锁版本, 下单检查及订单组等规则与LocalEngine共用
 pub struct Engine {
-    order_books: HashMap<String, RwLock<OrderBook>>,
+    /// 标的可在运行时增删, 查找时只短暂持有读锁, 撮合只锁定对应的标的
+    order_books: RwLock<HashMap<String, Arc<RwLock<OrderBook>>>>,
+    /// 账户手续费等级及风控, 只在检查订单时短暂加锁
+    accounts: Mutex<Accounts>,
+    /// 所有标的共用, 分配的订单号在Engine内不重复
+    order_ids: Arc<OrderIds>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Engine {
@@ -16,37 +37,301 @@ impl Engine {
     /// use match_trade::*;
     /// use rust_decimal::Decimal;
     /// use std::str::FromStr;
-    /// let mut engine = Engine::new();
-    /// let book = engine.add_book("DOGE/BTC",None);
+    /// let engine = Engine::new();
+    /// let book = engine.add_book("DOGE/BTC",None).unwrap();
     /// let order = Order::new(1,"DOGE/BTC",Decimal::from_str("1").unwrap(),Decimal::from_str("1").unwrap(),OrderKind::Limit,OrderSide::Buy,1000000000);
-    /// engine.add_order(order.clone());
-    /// engine.cancel_order("DOGE/BTC",order.id,order.price,order.side);
+    /// let order_id = engine.add_order(order.clone()).unwrap();
+    /// engine.cancel_order("DOGE/BTC",order_id,order.price,order.side);
     /// ```
     pub fn new() -> Self {
+        return Engine::with_clock(Arc::new(SystemClock));
+    }
+
+    /// 使用指定的时钟, 如回放或测试时使用ManualClock
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         return Engine {
-            order_books: HashMap::new(),
+            order_books: RwLock::new(HashMap::new()),
+            accounts: Mutex::new(Accounts::default()),
+            order_ids: Arc::new(OrderIds::default()),
+            clock,
         };
     }
 
+    /// 当前时间, 毫秒
+    pub fn now(&self) -> i64 {
+        return self.clock.now();
+    }
+
     /// 添加标的
     pub fn add_book(
-        &mut self,
+        &self,
         symbol: &str,
         result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
-    ) -> &RwLock<OrderBook> {
-        let book = OrderBook::new(symbol.to_string(), result_queue);
-        self.order_books.insert(symbol.to_string(), RwLock::new(book));
-        return self.order_books.get(symbol).unwrap();
+    ) -> Result<Arc<RwLock<OrderBook>>, &'static str> {
+        return self.add_instrument(Instrument::new(symbol), result_queue);
     }
 
-    /// 新建order
-    pub fn add_order(&self, order: Order) -> Result<bool, &'static str> {
-        if let Some(book) = self.order_books.get(&order.symbol) {
-            return book.write().unwrap().add_order(order);
+    /// 按标的配置添加标的
+    pub fn add_instrument(
+        &self,
+        instrument: Instrument,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Result<Arc<RwLock<OrderBook>>, &'static str> {
+        instrument.check()?;
+        let book = OrderBook::with_instrument(instrument, result_queue);
+        return self.insert_book(Arc::new(RwLock::new(book)));
+    }
+
+    /// 加入已有的标的, 挂单保持不变, 订单簿改用Engine的时钟及订单号分配器
+    pub fn insert_book(
+        &self,
+        book: Arc<RwLock<OrderBook>>,
+    ) -> Result<Arc<RwLock<OrderBook>>, &'static str> {
+        let symbol = book.read().unwrap().symbol.clone();
+        let mut order_books = self.order_books.write().unwrap();
+        if order_books.contains_key(&symbol) {
+            return Err("duplicate symbol");
+        }
+        {
+            let mut book = book.write().unwrap();
+            book.clock = self.clock.clone();
+            book.set_order_ids(self.order_ids.clone());
+        }
+        order_books.insert(symbol, book.clone());
+        return Ok(book);
+    }
+
+    /// 移出标的, 不撤销挂单, 用于迁移到其他Engine
+    pub fn take_book(&self, symbol: &str) -> Option<Arc<RwLock<OrderBook>>> {
+        return self.order_books.write().unwrap().remove(symbol);
+    }
+
+    /// 下架标的, 撤销所有挂单并返回
+    pub fn remove_book(&self, symbol: &str) -> Result<Vec<Order>, &'static str> {
+        // 先从索引移除, 新订单不再进入该标的
+        let book = match self.order_books.write().unwrap().remove(symbol) {
+            Some(book) => book,
+            None => return Err("not found symbol"),
+        };
+        let mut book = book.write().unwrap();
+        book.instrument.status = TradingStatus::Halted;
+        return Ok(book.mass_cancel(&CancelFilter::default()));
+    }
+
+    /// 标的改名, 挂单的symbol同步修改
+    pub fn rename_book(&self, symbol: &str, new_symbol: &str) -> Result<bool, &'static str> {
+        let mut order_books = self.order_books.write().unwrap();
+        if order_books.contains_key(new_symbol) {
+            return Err("duplicate symbol");
+        }
+        let book = match order_books.remove(symbol) {
+            Some(book) => book,
+            None => return Err("not found symbol"),
+        };
+        book.write().unwrap().rename(new_symbol);
+        order_books.insert(new_symbol.to_string(), book);
+        return Ok(true);
+    }
+
+    /// 替换标的结果队列
+    pub fn set_result_queue(
+        &self,
+        symbol: &str,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Result<bool, &'static str> {
+        if let Some(book) = self.book(symbol) {
+            book.write().unwrap().result_queue = result_queue;
+            return Ok(true);
         } else {
             return Err("not found symbol");
         }
     }
+
+    /// 获取标的
+    pub fn book(&self, symbol: &str) -> Option<Arc<RwLock<OrderBook>>> {
+        return self.order_books.read().unwrap().get(symbol).cloned();
+    }
+
+    /// 当前所有标的, 返回后不再持有索引锁
+    fn books(&self) -> Vec<(String, Arc<RwLock<OrderBook>>)> {
+        return self
+            .order_books
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+    }
+
+    /// 查看所有标的配置
+    pub fn symbols(&self) -> Vec<Instrument> {
+        let mut symbols: Vec<Instrument> = self
+            .books()
+            .iter()
+            .map(|(_, v)| v.read().unwrap().instrument.clone())
+            .collect();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        return symbols;
+    }
+
+    /// 设置交易状态
+    pub fn set_trading_status(&self, symbol: &str, status: TradingStatus) -> Result<bool, &'static str> {
+        if let Some(book) = self.book(symbol) {
+            book.write().unwrap().instrument.status = status;
+            return Ok(true);
+        } else {
+            return Err("not found symbol");
+        }
+    }
+
+    /// 设置标的手续费
+    pub fn set_fee_schedule(&self, symbol: &str, fee_schedule: FeeSchedule) -> Result<bool, &'static str> {
+        if let Some(book) = self.book(symbol) {
+            book.write().unwrap().fee_schedule = fee_schedule;
+            return Ok(true);
+        } else {
+            return Err("not found symbol");
+        }
+    }
+
+    /// 设置账户手续费等级, 对之后提交的订单生效
+    pub fn set_fee_tier(&self, owner: u64, tier: u32) {
+        self.accounts.lock().unwrap().set_fee_tier(owner, tier);
+    }
+
+    /// 设置默认风控限制
+    pub fn set_default_risk_limits(&self, limits: RiskLimits) {
+        self.accounts.lock().unwrap().risk.default_limits = limits;
+    }
+
+    /// 设置账户风控限制
+    pub fn set_risk_limits(&self, owner: u64, limits: RiskLimits) {
+        self.accounts.lock().unwrap().risk.set_limits(owner, limits);
+    }
+
+    /// 查看盘口深度
+    pub fn depth(&self, symbol: &str, limit: usize) -> Option<Depth> {
+        if let Some(book) = self.book(symbol) {
+            return Some(book.read().unwrap().depth(limit));
+        }
+        return None;
+    }
+
+    /// 查看挂单
+    pub fn get_order(&self, symbol: &str, order_id: u64) -> Option<Order> {
+        if let Some(book) = self.book(symbol) {
+            return book.read().unwrap().get_order(order_id).cloned();
+        }
+        return None;
+    }
+
+    /// 按订单号或客户订单号查看挂单
+    pub fn find_order(&self, symbol: &str, order_ref: &OrderRef) -> Option<Order> {
+        let book = self.book(symbol)?;
+        return book.read().unwrap().find_order(order_ref).cloned();
+    }
+
+    /// 最近limit笔成交, 按时间倒序
+    pub fn recent_trades(&self, symbol: &str, limit: usize) -> Option<Vec<TradeRecord>> {
+        let book = self.book(symbol)?;
+        return Some(book.read().unwrap().trade_tape.recent(limit));
+    }
+
+    /// 设置标的保留的最近成交数
+    pub fn set_tape_capacity(&self, symbol: &str, capacity: usize) -> Result<bool, &'static str> {
+        if let Some(book) = self.book(symbol) {
+            book.write().unwrap().trade_tape.set_capacity(capacity);
+            return Ok(true);
+        } else {
+            return Err("not found symbol");
+        }
+    }
+
+    /// 按订单号或客户订单号查看订单状态及成交明细, 包括已结束且仍在保留期内的订单
+    pub fn order_status(&self, symbol: &str, order_ref: &OrderRef) -> Option<OrderRecord> {
+        let book = self.book(symbol)?;
+        return book.read().unwrap().order_status(order_ref);
+    }
+
+    /// 设置标的保留的已结束订单数
+    pub fn set_order_retention(&self, symbol: &str, retention: usize) -> Result<bool, &'static str> {
+        if let Some(book) = self.book(symbol) {
+            book.write().unwrap().order_store.set_retention(retention);
+            return Ok(true);
+        } else {
+            return Err("not found symbol");
+        }
+    }
+
+    /// 查看账户挂单, symbol为None时查询所有标的
+    pub fn open_orders(&self, symbol: Option<&str>, owner: u64) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for (k, book) in self.books() {
+            if symbol.is_some_and(|v| v != k) {
+                continue;
+            }
+            orders.append(&mut book.read().unwrap().open_orders(owner));
+        }
+        return orders;
+    }
+
+    /// 查看账户在标的上的挂单及持仓
+    pub fn exposure(&self, symbol: &str, owner: u64) -> Option<Exposure> {
+        if let Some(book) = self.book(symbol) {
+            return book.read().unwrap().risk_state.exposure(owner);
+        }
+        return None;
+    }
+
+    /// 新建order, 返回订单号, 订单号为0时由Engine分配, 所有标的不重复
+    pub fn add_order(&self, order: Order) -> Result<u64, &'static str> {
+        let book = self.book(&order.symbol).ok_or("not found symbol")?;
+        let mut book = book.write().unwrap();
+        return local_engine::add_order(&mut book, order, |book, order| self.check_order(book, order));
+    }
+
+    /// 加锁检查账户, 检查完即释放
+    fn check_order(&self, book: &OrderBook, order: &mut Order) -> Result<(), &'static str> {
+        let now = self.clock.now();
+        return self.accounts.lock().unwrap().check_order(book, order, now);
+    }
+
+    /// 加锁检查改单后的价格数量, 检查完即释放
+    fn check_amend(
+        &self,
+        book: &OrderBook,
+        order_id: u64,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<(), &'static str> {
+        let now = self.clock.now();
+        return self.accounts.lock().unwrap().check_amend(book, order_id, new_price, new_volumn, now);
+    }
+
+    /// 新建OCO订单组, 一笔订单成交或撤销后撤销其余订单, 订单须属于同一标的, 返回group_id
+    pub fn add_oco(&self, orders: Vec<Order>) -> Result<u64, &'static str> {
+        let book = self.book(group_symbol(orders.iter())?).ok_or("not found symbol")?;
+        let mut book = book.write().unwrap();
+        return local_engine::add_oco(&mut book, orders, |book, order| self.check_order(book, order));
+    }
+
+    /// 新建OTO订单组, 父单全部成交后子单生效, 多个子单组成OCO, 返回group_id
+    ///
+    /// 如止盈止损: 父单为开仓单, 子单为止盈限价单及止损单
+    pub fn add_oto(&self, parent: Order, children: Vec<Order>) -> Result<u64, &'static str> {
+        let symbol = group_symbol(std::iter::once(&parent).chain(children.iter()))?;
+        let book = self.book(symbol).ok_or("not found symbol")?;
+        let mut book = book.write().unwrap();
+        return local_engine::add_oto(&mut book, parent, children, |book, order| {
+            self.check_order(book, order)
+        });
+    }
+
+    /// 查看订单组
+    pub fn order_group(&self, symbol: &str, group_id: u64) -> Option<OrderGroup> {
+        let book = self.book(symbol)?;
+        return book.read().unwrap().group(group_id).cloned();
+    }
     
     /// 撤销order
     pub fn cancel_order(
@@ -56,18 +341,82 @@ impl Engine {
         price: Decimal,
         side: OrderSide,
     ) -> Option<Order> {
-        if let Some(book) = self.order_books.get(symbol) {
+        if let Some(book) = self.book(symbol) {
             return book.write().unwrap().calcen_order(order_id, price, side);
         } else {
             return None;
         }
     }
 
+    /// 修改order
+    pub fn amend_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let book = self.book(symbol).ok_or("not found symbol")?;
+        let mut book = book.write().unwrap();
+        return local_engine::amend_order(
+            &mut book,
+            order_id,
+            price,
+            side,
+            new_price,
+            new_volumn,
+            |book, order_id, new_price, new_volumn| {
+                self.check_amend(book, order_id, new_price, new_volumn)
+            },
+        );
+    }
+
+    /// 按订单号或客户订单号撤单
+    pub fn cancel_by_ref(&self, symbol: &str, order_ref: &OrderRef) -> Option<Order> {
+        let book = self.book(symbol)?;
+        return book.write().unwrap().cancel_by_ref(order_ref);
+    }
+
+    /// 按订单号或客户订单号改单
+    pub fn amend_by_ref(
+        &self,
+        symbol: &str,
+        order_ref: &OrderRef,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let book = self.book(symbol).ok_or("not found symbol")?;
+        let mut book = book.write().unwrap();
+        return local_engine::amend_by_ref(
+            &mut book,
+            order_ref,
+            new_price,
+            new_volumn,
+            |book, order_id, new_price, new_volumn| {
+                self.check_amend(book, order_id, new_price, new_volumn)
+            },
+        );
+    }
+
+    /// 批量撤单, 返回被撤销的订单
+    pub fn mass_cancel(&self, filter: &CancelFilter) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for (symbol, book) in self.books() {
+            if filter.symbol.as_ref().is_some_and(|v| *v != symbol) {
+                continue;
+            }
+            orders.append(&mut book.write().unwrap().mass_cancel(filter));
+        }
+        return orders;
+    }
+
     /// 查看买单，卖单总订单数
     pub fn len(&self) -> (usize, usize) {
         let mut buy = 0;
         let mut sell = 0;
-        for (_, v) in &self.order_books {
+        for (_, v) in self.books() {
             let book = v.read().unwrap();
             buy += book.buy_queue.len();
             sell += book.sell_queue.len()
@@ -76,3 +425,9 @@ impl Engine {
     }
     
 }
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use rust_decimal::{prelude::Zero, Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+/// 手续费币种
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum FeeAsset {
+    /// 按成交数量收取 (基础币)
+    Base,
+    /// 按成交金额收取 (计价币)
+    Quote,
+}
+
+/// 费率, 负数表示返佣
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FeeRate {
+    pub maker: Decimal,
+    pub taker: Decimal,
+}
+
+impl FeeRate {
+    pub fn new(maker: Decimal, taker: Decimal) -> FeeRate {
+        FeeRate { maker, taker }
+    }
+}
+
+/// 标的手续费配置, 按账户等级区分费率
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FeeSchedule {
+    pub asset: FeeAsset,
+    /// 手续费保留小数位
+    pub scale: u32,
+    pub default_rate: FeeRate,
+    pub tiers: HashMap<u32, FeeRate>,
+}
+
+impl FeeSchedule {
+    /// # Examples
+    ///
+    /// ```
+    /// use match_trade::*;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    /// let schedule = FeeSchedule::new(
+    ///     FeeAsset::Quote,
+    ///     8,
+    ///     FeeRate::new(Decimal::from_str("0.001").unwrap(), Decimal::from_str("0.002").unwrap()),
+    /// )
+    /// .with_tier(1, FeeRate::new(Decimal::from_str("-0.0001").unwrap(), Decimal::from_str("0.001").unwrap()));
+    /// assert_eq!(schedule.maker_fee(1, Decimal::from(100), Decimal::from(2)), Decimal::from_str("-0.02").unwrap());
+    /// ```
+    pub fn new(asset: FeeAsset, scale: u32, default_rate: FeeRate) -> FeeSchedule {
+        FeeSchedule {
+            asset,
+            scale,
+            default_rate,
+            tiers: HashMap::new(),
+        }
+    }
+
+    /// 设置账户等级费率
+    pub fn with_tier(mut self, tier: u32, rate: FeeRate) -> FeeSchedule {
+        self.tiers.insert(tier, rate);
+        self
+    }
+
+    /// 获取账户等级费率, 未配置的等级使用默认费率
+    pub fn rate(&self, tier: u32) -> &FeeRate {
+        self.tiers.get(&tier).unwrap_or(&self.default_rate)
+    }
+
+    /// 计算maker手续费
+    pub fn maker_fee(&self, tier: u32, price: Decimal, volumn: Decimal) -> Decimal {
+        self.fee(self.rate(tier).maker, price, volumn)
+    }
+
+    /// 计算taker手续费
+    pub fn taker_fee(&self, tier: u32, price: Decimal, volumn: Decimal) -> Decimal {
+        self.fee(self.rate(tier).taker, price, volumn)
+    }
+
+    fn fee(&self, rate: Decimal, price: Decimal, volumn: Decimal) -> Decimal {
+        let amount = match self.asset {
+            FeeAsset::Base => volumn,
+            FeeAsset::Quote => price * volumn,
+        };
+        let fee = amount * rate;
+        // 收费向上取整, 返佣向零取整, 多出的精度始终归交易所
+        if fee.is_sign_negative() {
+            return fee.round_dp_with_strategy(self.scale, RoundingStrategy::ToZero);
+        }
+        return fee.round_dp_with_strategy(self.scale, RoundingStrategy::AwayFromZero);
+    }
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        FeeSchedule::new(
+            FeeAsset::Quote,
+            8,
+            FeeRate::new(Decimal::zero(), Decimal::zero()),
+        )
+    }
+}
@@ -0,0 +1,56 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::Order;
+
+/// 订单组类型
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum GroupKind {
+    /// 一笔成交或撤销后撤销其余订单
+    Oco,
+    /// 父单全部成交后子单生效, 多个子单生效后组成OCO
+    Oto,
+}
+
+/// 组内已生效的订单
+#[derive(Clone, Debug, Serialize)]
+pub struct GroupLeg {
+    pub order_id: u64,
+    /// 下单数量, 改单后更新
+    pub volumn: Decimal,
+    /// 已成交数量
+    pub filled: Decimal,
+}
+
+/// 订单组, id为第一笔订单的id
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderGroup {
+    pub id: u64,
+    pub kind: GroupKind,
+    pub legs: Vec<GroupLeg>,
+    /// 等待父单成交的子单
+    pub pending: Vec<Order>,
+}
+
+impl OrderGroup {
+    pub(crate) fn new(id: u64, kind: GroupKind, legs: &[Order], pending: Vec<Order>) -> OrderGroup {
+        let legs = legs
+            .iter()
+            .map(|v| GroupLeg {
+                order_id: v.id,
+                volumn: v.volumn,
+                filled: Decimal::ZERO,
+            })
+            .collect();
+        OrderGroup {
+            id,
+            kind,
+            legs,
+            pending,
+        }
+    }
+
+    pub(crate) fn leg_mut(&mut self, order_id: u64) -> Option<&mut GroupLeg> {
+        return self.legs.iter_mut().find(|v| v.order_id == order_id);
+    }
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use rust_decimal::{prelude::Zero, Decimal};
+use serde::{Deserialize, Serialize};
+
+use crate::{Order, OrderKind};
+
+/// 交易状态
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum TradingStatus {
+    Trading,
+    /// 暂停交易, 只允许撤单
+    Halted,
+}
+
+/// 标的配置
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Instrument {
+    pub symbol: String,
+    /// 最小价格变动单位
+    pub tick_size: Decimal,
+    /// 最小数量变动单位
+    pub lot_size: Decimal,
+    /// 最小下单数量
+    pub min_volumn: Decimal,
+    pub status: TradingStatus,
+}
+
+impl Instrument {
+    pub fn new(symbol: &str) -> Instrument {
+        Instrument {
+            symbol: symbol.to_string(),
+            tick_size: Decimal::from_str("0.00000001").unwrap(),
+            lot_size: Decimal::from_str("0.00000001").unwrap(),
+            min_volumn: Decimal::zero(),
+            status: TradingStatus::Trading,
+        }
+    }
+
+    /// 检查配置, tick_size和lot_size须大于0, min_volumn不能为负
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.tick_size <= Decimal::zero() {
+            return Err("invalid tick_size");
+        }
+        if self.lot_size <= Decimal::zero() {
+            return Err("invalid lot_size");
+        }
+        if self.min_volumn < Decimal::zero() {
+            return Err("invalid min_volumn");
+        }
+        Ok(())
+    }
+
+    /// 检查交易状态及订单价格数量精度
+    pub fn validate(&self, order: &Order) -> Result<(), &'static str> {
+        if self.status != TradingStatus::Trading {
+            return Err("trading halted");
+        }
+        return self.check_precision(order.kind, order.price, order.volumn);
+    }
+
+    /// 检查价格数量精度, 市价单不检查价格
+    pub fn check_precision(
+        &self,
+        kind: OrderKind,
+        price: Decimal,
+        volumn: Decimal,
+    ) -> Result<(), &'static str> {
+        if volumn <= Decimal::zero() || !(volumn % self.lot_size).is_zero() {
+            return Err("invalid volumn");
+        }
+        if volumn < self.min_volumn {
+            return Err("volumn below minimum");
+        }
+        if kind != OrderKind::Market
+            && (price <= Decimal::zero() || !(price % self.tick_size).is_zero())
+        {
+            return Err("invalid price");
+        }
+        Ok(())
+    }
+}
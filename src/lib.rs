@@ -1,11 +1,44 @@
+#![allow(clippy::needless_return)]
+#[cfg(feature = "async")]
+mod async_engine;
+mod clock;
 mod engine;
+mod fee;
+mod group;
+mod instrument;
+mod local_engine;
 mod order_book;
 mod order;
 mod order_queue;
+mod peg;
 mod queue;
+mod risk;
+mod scale;
+mod sequencer;
+mod shard;
+mod stop;
+mod store;
+mod symbol;
+mod tape;
 
 use order_queue::*;
+#[cfg(feature = "async")]
+pub use crate::async_engine::*;
 pub use order_book::*;
+pub use crate::clock::*;
 pub use crate::engine::*;
+pub use crate::fee::*;
+pub use crate::group::*;
+pub use crate::instrument::*;
+pub use crate::local_engine::*;
 pub use crate::order::*;
+pub use crate::peg::*;
 pub use crate::queue::*;
+pub use crate::risk::*;
+pub use crate::scale::*;
+pub use crate::sequencer::*;
+pub use crate::shard::*;
+pub use crate::stop::*;
+pub use crate::store::*;
+pub use crate::symbol::*;
+pub use crate::tape::*;
@@ -0,0 +1,537 @@
+use std::{collections::HashMap, sync::Arc};
+
+use rust_decimal::Decimal;
+
+use crate::{
+    CancelFilter, Clock, Depth, Exposure, FeeSchedule, Instrument, Order, OrderBook, OrderGroup,
+    OrderIds, OrderRecord, OrderRef, OrderSide, ResultQueue, RiskLimits, RiskManager, Symbol, SystemClock,
+    TradeRecord, TradingStatus,
+};
+
+/// 账户手续费等级及风控, LocalEngine直接持有, Engine加锁后使用
+#[derive(Default)]
+pub(crate) struct Accounts {
+    fee_tiers: HashMap<u64, u32>,
+    pub(crate) risk: RiskManager,
+}
+
+impl Accounts {
+    pub(crate) fn set_fee_tier(&mut self, owner: u64, tier: u32) {
+        self.fee_tiers.insert(owner, tier);
+    }
+
+    /// 写入手续费等级, 检查交易状态 精度及风控
+    pub(crate) fn check_order(
+        &mut self,
+        book: &OrderBook,
+        order: &mut Order,
+        now: i64,
+    ) -> Result<(), &'static str> {
+        order.fee_tier = *self.fee_tiers.get(&order.owner).unwrap_or(&0);
+        book.price_peg(order)?;
+        book.instrument.validate(order)?;
+        self.risk.check_rate(order.owner, now)?;
+        return book.check_risk(order, self.risk.limits(order.owner));
+    }
+
+    /// 按新订单的规则检查改单后的价格数量及下单频率
+    pub(crate) fn check_amend(
+        &mut self,
+        book: &OrderBook,
+        order_id: u64,
+        new_price: Decimal,
+        new_volumn: Decimal,
+        now: i64,
+    ) -> Result<(), &'static str> {
+        let order = book.get_order(order_id).ok_or("not found order")?;
+        book.instrument.check_precision(order.kind, new_price, new_volumn)?;
+        self.risk.check_rate(order.owner, now)?;
+        return book.check_amend_risk(order_id, new_price, new_volumn, self.risk.limits(order.owner));
+    }
+}
+
+/// 分配订单号并检查后下单, 未通过检查时拒绝该订单, 返回订单号
+///
+/// LocalEngine与Engine共用, check为账户检查, Engine在check内短暂持有账户锁
+pub(crate) fn add_order<F>(book: &mut OrderBook, mut order: Order, check: F) -> Result<u64, &'static str>
+where
+    F: FnOnce(&OrderBook, &mut Order) -> Result<(), &'static str>,
+{
+    book.assign_order_id(&mut order)?;
+    if let Err(e) = check(book, &mut order) {
+        return book.reject(&order, e).map(|_| order.id);
+    }
+    return book.add_order(order);
+}
+
+/// 检查后改单, 未通过检查时不修改订单
+///
+/// check为账户检查, 参数为订单号及新价格数量
+pub(crate) fn amend_order<F>(
+    book: &mut OrderBook,
+    order_id: u64,
+    price: Decimal,
+    side: OrderSide,
+    new_price: Decimal,
+    new_volumn: Decimal,
+    check: F,
+) -> Result<bool, &'static str>
+where
+    F: FnOnce(&OrderBook, u64, Decimal, Decimal) -> Result<(), &'static str>,
+{
+    check(book, order_id, new_price, new_volumn)?;
+    return book.amend_order(order_id, price, side, new_price, new_volumn);
+}
+
+/// 检查后按订单号或客户订单号改单
+pub(crate) fn amend_by_ref<F>(
+    book: &mut OrderBook,
+    order_ref: &OrderRef,
+    new_price: Decimal,
+    new_volumn: Decimal,
+    check: F,
+) -> Result<bool, &'static str>
+where
+    F: FnOnce(&OrderBook, u64, Decimal, Decimal) -> Result<(), &'static str>,
+{
+    let (order_id, price, side) = book
+        .find_order(order_ref)
+        .map(|v| (v.id, v.price, v.side))
+        .ok_or("not found order")?;
+    return amend_order(book, order_id, price, side, new_price, new_volumn, check);
+}
+
+/// 订单组所属的标的, 订单须属于同一标的
+pub(crate) fn group_symbol<'a>(mut orders: impl Iterator<Item = &'a Order>) -> Result<&'a str, &'static str> {
+    let symbol = match orders.next() {
+        Some(order) => order.symbol.as_str(),
+        None => return Err("empty order group"),
+    };
+    if orders.any(|v| v.symbol != symbol) {
+        return Err("order group must share a symbol");
+    }
+    return Ok(symbol);
+}
+
+/// 检查订单组的所有订单, 任意一笔未通过时拒绝该订单, 整组不下单
+fn check_group<'a, F>(
+    book: &mut OrderBook,
+    orders: impl Iterator<Item = &'a mut Order>,
+    check: &mut F,
+) -> Result<(), &'static str>
+where
+    F: FnMut(&OrderBook, &mut Order) -> Result<(), &'static str>,
+{
+    for order in orders {
+        if let Err(e) = check(book, order) {
+            let _ = book.reject(order, e);
+            return Err(e);
+        }
+    }
+    return Ok(());
+}
+
+/// 检查后新建OCO订单组, 返回group_id
+pub(crate) fn add_oco<F>(book: &mut OrderBook, mut orders: Vec<Order>, mut check: F) -> Result<u64, &'static str>
+where
+    F: FnMut(&OrderBook, &mut Order) -> Result<(), &'static str>,
+{
+    for order in orders.iter_mut() {
+        book.assign_order_id(order)?;
+    }
+    check_group(book, orders.iter_mut(), &mut check)?;
+    return book.add_oco(orders);
+}
+
+/// 检查后新建OTO订单组, 返回group_id
+pub(crate) fn add_oto<F>(
+    book: &mut OrderBook,
+    mut parent: Order,
+    mut children: Vec<Order>,
+    mut check: F,
+) -> Result<u64, &'static str>
+where
+    F: FnMut(&OrderBook, &mut Order) -> Result<(), &'static str>,
+{
+    for order in std::iter::once(&mut parent).chain(children.iter_mut()) {
+        book.assign_order_id(order)?;
+    }
+    check_group(book, std::iter::once(&mut parent).chain(children.iter_mut()), &mut check)?;
+    return book.add_oto(parent, children);
+}
+
+/// 在分片间迁移的标的
+pub(crate) struct MovedBook {
+    book: OrderBook,
+    commands: Option<u64>,
+}
+
+/// 单线程Engine
+///
+/// 独占所有订单簿, 方法直接修改订单簿, 不加锁. 由Sequencer或ShardedEngine的撮合线程持有,
+/// 也可在单线程场景下直接使用. 下单检查与Engine共用同一套规则
+pub struct LocalEngine {
+    order_books: HashMap<String, OrderBook>,
+    accounts: Accounts,
+    /// 所有标的共用, ShardedEngine的各分片共用同一个
+    pub(crate) order_ids: Arc<OrderIds>,
+    clock: Arc<dyn Clock>,
+    /// 各标的已执行命令数, 由ShardedEngine统计
+    pub(crate) commands: HashMap<Symbol, u64>,
+}
+
+impl LocalEngine {
+    /// # Examples
+    ///
+    /// ```
+    /// use match_trade::*;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    /// let mut engine = LocalEngine::new();
+    /// engine.add_book("DOGE/BTC",None).unwrap();
+    /// let order = Order::new(1,"DOGE/BTC",Decimal::from_str("1").unwrap(),Decimal::from_str("1").unwrap(),OrderKind::Limit,OrderSide::Buy,1000000000);
+    /// engine.add_order(order.clone()).unwrap();
+    /// engine.cancel_order("DOGE/BTC",order.id,order.price,order.side).unwrap();
+    /// ```
+    pub fn new() -> Self {
+        return LocalEngine::with_clock(Arc::new(SystemClock));
+    }
+
+    /// 使用指定的时钟
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        return LocalEngine {
+            order_books: HashMap::new(),
+            accounts: Accounts::default(),
+            order_ids: Arc::new(OrderIds::default()),
+            clock,
+            commands: HashMap::new(),
+        };
+    }
+
+    /// 当前时间, 毫秒
+    pub fn now(&self) -> i64 {
+        return self.clock.now();
+    }
+
+    /// 添加标的
+    pub fn add_book(
+        &mut self,
+        symbol: &str,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Result<bool, &'static str> {
+        return self.add_instrument(Instrument::new(symbol), result_queue);
+    }
+
+    /// 按标的配置添加标的
+    pub fn add_instrument(
+        &mut self,
+        instrument: Instrument,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Result<bool, &'static str> {
+        instrument.check()?;
+        if self.order_books.contains_key(&instrument.symbol) {
+            return Err("duplicate symbol");
+        }
+        return self.insert_book(OrderBook::with_instrument(instrument, result_queue));
+    }
+
+    /// 加入已有的标的, 挂单保持不变, 订单簿改用LocalEngine的时钟及订单号分配器
+    pub fn insert_book(&mut self, mut book: OrderBook) -> Result<bool, &'static str> {
+        if self.order_books.contains_key(&book.symbol) {
+            return Err("duplicate symbol");
+        }
+        book.clock = self.clock.clone();
+        book.set_order_ids(self.order_ids.clone());
+        self.order_books.insert(book.symbol.clone(), book);
+        return Ok(true);
+    }
+
+    /// 移出标的, 不撤销挂单, 用于迁移到其他LocalEngine
+    pub fn take_book(&mut self, symbol: &str) -> Option<OrderBook> {
+        return self.order_books.remove(symbol);
+    }
+
+    /// 下架标的, 撤销所有挂单并返回
+    pub fn remove_book(&mut self, symbol: &str) -> Result<Vec<Order>, &'static str> {
+        let mut book = self.order_books.remove(symbol).ok_or("not found symbol")?;
+        self.commands.remove(symbol);
+        book.instrument.status = TradingStatus::Halted;
+        return Ok(book.mass_cancel(&CancelFilter::default()));
+    }
+
+    /// 标的改名, 挂单的symbol同步修改
+    pub fn rename_book(&mut self, symbol: &str, new_symbol: &str) -> Result<bool, &'static str> {
+        if self.order_books.contains_key(new_symbol) {
+            return Err("duplicate symbol");
+        }
+        let mut book = self.order_books.remove(symbol).ok_or("not found symbol")?;
+        book.rename(new_symbol);
+        self.order_books.insert(new_symbol.to_string(), book);
+        if let Some(count) = self.commands.remove(symbol) {
+            self.commands.insert(Symbol::new(new_symbol), count);
+        }
+        return Ok(true);
+    }
+
+    /// 获取标的
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        return self.order_books.get(symbol);
+    }
+
+    pub fn book_mut(&mut self, symbol: &str) -> Option<&mut OrderBook> {
+        return self.order_books.get_mut(symbol);
+    }
+
+    /// 查看所有标的配置
+    pub fn symbols(&self) -> Vec<Instrument> {
+        let mut symbols: Vec<Instrument> = self
+            .order_books
+            .values()
+            .map(|v| v.instrument.clone())
+            .collect();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        return symbols;
+    }
+
+    /// 设置交易状态
+    pub fn set_trading_status(&mut self, symbol: &str, status: TradingStatus) -> Result<bool, &'static str> {
+        let book = self.book_mut(symbol).ok_or("not found symbol")?;
+        book.instrument.status = status;
+        return Ok(true);
+    }
+
+    /// 设置标的手续费
+    pub fn set_fee_schedule(&mut self, symbol: &str, fee_schedule: FeeSchedule) -> Result<bool, &'static str> {
+        let book = self.book_mut(symbol).ok_or("not found symbol")?;
+        book.fee_schedule = fee_schedule;
+        return Ok(true);
+    }
+
+    /// 设置账户手续费等级, 对之后提交的订单生效
+    pub fn set_fee_tier(&mut self, owner: u64, tier: u32) {
+        self.accounts.set_fee_tier(owner, tier);
+    }
+
+    /// 设置默认风控限制
+    pub fn set_default_risk_limits(&mut self, limits: RiskLimits) {
+        self.accounts.risk.default_limits = limits;
+    }
+
+    /// 设置账户风控限制
+    pub fn set_risk_limits(&mut self, owner: u64, limits: RiskLimits) {
+        self.accounts.risk.set_limits(owner, limits);
+    }
+
+    /// 查看盘口深度
+    pub fn depth(&self, symbol: &str, limit: usize) -> Option<Depth> {
+        return self.book(symbol).map(|v| v.depth(limit));
+    }
+
+    /// 查看挂单
+    pub fn get_order(&self, symbol: &str, order_id: u64) -> Option<Order> {
+        return self.book(symbol)?.get_order(order_id).cloned();
+    }
+
+    /// 按订单号或客户订单号查看挂单
+    pub fn find_order(&self, symbol: &str, order_ref: &OrderRef) -> Option<Order> {
+        return self.book(symbol)?.find_order(order_ref).cloned();
+    }
+
+    /// 最近limit笔成交, 按时间倒序
+    pub fn recent_trades(&self, symbol: &str, limit: usize) -> Option<Vec<TradeRecord>> {
+        return self.book(symbol).map(|v| v.trade_tape.recent(limit));
+    }
+
+    /// 设置标的保留的最近成交数
+    pub fn set_tape_capacity(&mut self, symbol: &str, capacity: usize) -> Result<bool, &'static str> {
+        let book = self.book_mut(symbol).ok_or("not found symbol")?;
+        book.trade_tape.set_capacity(capacity);
+        return Ok(true);
+    }
+
+    /// 按订单号或客户订单号查看订单状态及成交明细, 包括已结束且仍在保留期内的订单
+    pub fn order_status(&self, symbol: &str, order_ref: &OrderRef) -> Option<OrderRecord> {
+        return self.book(symbol)?.order_status(order_ref);
+    }
+
+    /// 设置标的保留的已结束订单数
+    pub fn set_order_retention(&mut self, symbol: &str, retention: usize) -> Result<bool, &'static str> {
+        let book = self.book_mut(symbol).ok_or("not found symbol")?;
+        book.order_store.set_retention(retention);
+        return Ok(true);
+    }
+
+    /// 查看账户挂单, symbol为None时查询所有标的
+    pub fn open_orders(&self, symbol: Option<&str>, owner: u64) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for (k, book) in self.order_books.iter() {
+            if symbol.is_some_and(|v| v != k) {
+                continue;
+            }
+            orders.append(&mut book.open_orders(owner));
+        }
+        return orders;
+    }
+
+    /// 查看账户在标的上的挂单及持仓
+    pub fn exposure(&self, symbol: &str, owner: u64) -> Option<Exposure> {
+        return self.book(symbol)?.risk_state.exposure(owner);
+    }
+
+    /// 新建order, 返回订单号, 订单号为0时由LocalEngine分配, 所有标的不重复
+    pub fn add_order(&mut self, order: Order) -> Result<u64, &'static str> {
+        let now = self.clock.now();
+        let book = self.order_books.get_mut(order.symbol.as_str()).ok_or("not found symbol")?;
+        let accounts = &mut self.accounts;
+        return add_order(book, order, |book, order| accounts.check_order(book, order, now));
+    }
+
+    /// 新建OCO订单组, 一笔订单成交或撤销后撤销其余订单, 订单须属于同一标的, 返回group_id
+    pub fn add_oco(&mut self, orders: Vec<Order>) -> Result<u64, &'static str> {
+        let now = self.clock.now();
+        let symbol = group_symbol(orders.iter())?;
+        let book = self.order_books.get_mut(symbol).ok_or("not found symbol")?;
+        let accounts = &mut self.accounts;
+        return add_oco(book, orders, |book, order| accounts.check_order(book, order, now));
+    }
+
+    /// 新建OTO订单组, 父单全部成交后子单生效, 多个子单组成OCO, 返回group_id
+    pub fn add_oto(&mut self, parent: Order, children: Vec<Order>) -> Result<u64, &'static str> {
+        let now = self.clock.now();
+        let symbol = group_symbol(std::iter::once(&parent).chain(children.iter()))?;
+        let book = self.order_books.get_mut(symbol).ok_or("not found symbol")?;
+        let accounts = &mut self.accounts;
+        return add_oto(book, parent, children, |book, order| accounts.check_order(book, order, now));
+    }
+
+    /// 查看订单组
+    pub fn order_group(&self, symbol: &str, group_id: u64) -> Option<OrderGroup> {
+        return self.book(symbol)?.group(group_id).cloned();
+    }
+
+    /// 撤销order
+    pub fn cancel_order(
+        &mut self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+    ) -> Option<Order> {
+        return self.book_mut(symbol)?.calcen_order(order_id, price, side);
+    }
+
+    /// 修改order
+    pub fn amend_order(
+        &mut self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let now = self.clock.now();
+        let book = self.order_books.get_mut(symbol).ok_or("not found symbol")?;
+        let accounts = &mut self.accounts;
+        return amend_order(
+            book,
+            order_id,
+            price,
+            side,
+            new_price,
+            new_volumn,
+            |book, order_id, new_price, new_volumn| {
+                accounts.check_amend(book, order_id, new_price, new_volumn, now)
+            },
+        );
+    }
+
+    /// 按订单号或客户订单号撤单
+    pub fn cancel_by_ref(&mut self, symbol: &str, order_ref: &OrderRef) -> Option<Order> {
+        return self.book_mut(symbol)?.cancel_by_ref(order_ref);
+    }
+
+    /// 按订单号或客户订单号改单
+    pub fn amend_by_ref(
+        &mut self,
+        symbol: &str,
+        order_ref: &OrderRef,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let now = self.clock.now();
+        let book = self.order_books.get_mut(symbol).ok_or("not found symbol")?;
+        let accounts = &mut self.accounts;
+        return amend_by_ref(
+            book,
+            order_ref,
+            new_price,
+            new_volumn,
+            |book, order_id, new_price, new_volumn| {
+                accounts.check_amend(book, order_id, new_price, new_volumn, now)
+            },
+        );
+    }
+
+    /// 批量撤单, 返回被撤销的订单
+    pub fn mass_cancel(&mut self, filter: &CancelFilter) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for (symbol, book) in self.order_books.iter_mut() {
+            if filter.symbol.as_ref().is_some_and(|v| v != symbol) {
+                continue;
+            }
+            orders.append(&mut book.mass_cancel(filter));
+        }
+        return orders;
+    }
+
+    /// 查看买单，卖单总订单数
+    pub fn len(&self) -> (usize, usize) {
+        let mut buy = 0;
+        let mut sell = 0;
+        for book in self.order_books.values() {
+            buy += book.buy_queue.len();
+            sell += book.sell_queue.len();
+        }
+        return (buy, sell);
+    }
+
+    /// 移出标的及其命令数, 用于在分片间迁移
+    pub(crate) fn take_moved(&mut self, symbol: &str) -> Option<Box<MovedBook>> {
+        let book = self.order_books.remove(symbol)?;
+        let commands = self.commands.remove(symbol);
+        return Some(Box::new(MovedBook { book, commands }));
+    }
+
+    /// 加入迁移来的标的及其命令数, 标的已存在时原样返回
+    pub(crate) fn insert_moved(&mut self, moved: Box<MovedBook>) -> Result<(), Box<MovedBook>> {
+        if self.order_books.contains_key(&moved.book.symbol) {
+            return Err(moved);
+        }
+        let MovedBook { book, commands } = *moved;
+        if let Some(count) = commands {
+            self.commands.insert(Symbol::new(&book.symbol), count);
+        }
+        let _ = self.insert_book(book);
+        return Ok(());
+    }
+
+    /// 记录标的命令数, 标的不存在时不记录, 标的首次出现时才插入
+    pub(crate) fn record(&mut self, symbol: &Symbol) {
+        if !self.order_books.contains_key(symbol.as_str()) {
+            return;
+        }
+        match self.commands.get_mut(symbol.as_str()) {
+            Some(count) => *count += 1,
+            None => {
+                self.commands.insert(symbol.clone(), 1);
+            }
+        }
+    }
+}
+
+impl Default for LocalEngine {
+    fn default() -> Self {
+        LocalEngine::new()
+    }
+}
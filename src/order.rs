@@ -1,30 +1,58 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Deserialize, Serialize)]
+use crate::{Peg, Stop, Symbol};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
 pub enum OrderSide {
     Buy,
     Sell,
 }
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
 pub enum OrderKind {
     Limit,
     Market,
     IOC,//Immediate-Or-Cancel
     FOK,//Fill-Or-Kill
 }
-#[derive(Clone, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Order {
+    /// 订单号, 为0时下单时分配, 加入Engine的订单簿分配的订单号在Engine内不重复
     pub id: u64,
-    pub symbol: String,
+    pub symbol: Symbol,
     pub price: Decimal,
     pub volumn: Decimal,
     pub kind: OrderKind,
     pub side: OrderSide,
+    /// 客户端下单时间
     pub timestamp: i64,
+    /// 受理时间, 毫秒, 由订单簿按引擎时钟写入
+    pub accepted_at: Option<i64>,
+    /// 下单账户
+    pub owner: u64,
+    /// 客户订单号, 同一账户在标的内的未完成订单中唯一
+    pub client_order_id: Option<String>,
+    /// IOC单最小成交量, 不能成交该数量时整单拒绝
+    pub min_volumn: Option<Decimal>,
+    /// 全部成交或不成交, 下单时不能全部成交则不成交直接挂单, 挂单只与能使其全部成交的taker撮合
+    ///
+    /// 挂单不计入深度及最优价, 价格可能与对手盘交叉
+    pub all_or_none: bool,
+    /// 挂钩单参数, 价格由订单簿按盘口计算
+    pub peg: Option<Peg>,
+    /// 止损参数, 触发前不进入订单簿
+    pub stop: Option<Stop>,
+    /// 所属订单组, 由订单簿在组合下单时写入
+    pub group_id: Option<u64>,
+    /// 账户手续费等级, 由引擎在下单时写入
+    #[serde(skip)]
+    pub(crate) fee_tier: u32,
 }
 
 impl Order {
+    /// 已添加的标的共享驻留的名称, 未知标的的名称不驻留
     pub fn new(
         id: u64,
         symbol: &str,
@@ -36,12 +64,114 @@ impl Order {
     ) -> Order {
         Order {
             id,
-            symbol: symbol.to_string(),
-            price: price,
-            volumn: volumn,
+            symbol: Symbol::new(symbol),
+            price,
+            volumn,
             kind,
             side,
             timestamp,
+            accepted_at: None,
+            owner: 0,
+            client_order_id: None,
+            min_volumn: None,
+            all_or_none: false,
+            peg: None,
+            stop: None,
+            group_id: None,
+            fee_tier: 0,
         }
     }
+
+    /// 设置下单账户
+    pub fn with_owner(mut self, owner: u64) -> Order {
+        self.owner = owner;
+        self
+    }
+
+    /// 设置客户订单号
+    pub fn with_client_order_id(mut self, client_order_id: &str) -> Order {
+        self.client_order_id = Some(client_order_id.to_string());
+        self
+    }
+
+    /// 设置IOC单最小成交量
+    pub fn with_min_volumn(mut self, min_volumn: Decimal) -> Order {
+        self.min_volumn = Some(min_volumn);
+        self
+    }
+
+    /// 设置为挂钩单, 只对限价单有效
+    pub fn with_peg(mut self, peg: Peg) -> Order {
+        self.peg = Some(peg);
+        self
+    }
+
+    /// 设置为止损单, 只对限价单及市价单有效
+    pub fn with_stop(mut self, stop: Stop) -> Order {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// 设置为全部成交或不成交, 只对限价单有效
+    pub fn with_all_or_none(mut self) -> Order {
+        self.all_or_none = true;
+        self
+    }
+}
+
+/// 订单标识, 撤单 改单及查询时可使用订单号或客户订单号
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum OrderRef {
+    Id(u64),
+    Client { owner: u64, client_order_id: String },
+}
+
+impl OrderRef {
+    pub fn client(owner: u64, client_order_id: &str) -> OrderRef {
+        OrderRef::Client {
+            owner,
+            client_order_id: client_order_id.to_string(),
+        }
+    }
+}
+
+impl From<u64> for OrderRef {
+    fn from(order_id: u64) -> OrderRef {
+        OrderRef::Id(order_id)
+    }
+}
+
+/// 订单号分配器, 加入Engine的订单簿共用Engine的分配器, 分配的订单号在Engine内不重复
+#[derive(Debug, Default)]
+pub struct OrderIds {
+    /// 最近分配或使用的订单号
+    last: AtomicU64,
+}
+
+impl OrderIds {
+    /// 为订单号为0的订单分配订单号, 指定订单号的订单之后分配的订单号从其后开始
+    ///
+    /// 已分配到u64::MAX时返回错误, 不会回绕到已使用的订单号
+    pub fn assign(&self, order: &mut Order) -> Result<(), &'static str> {
+        if order.id == 0 {
+            let last = self
+                .last
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| v.checked_add(1))
+                .map_err(|_| "order id exhausted")?;
+            order.id = last + 1;
+        } else {
+            self.last.fetch_max(order.id, Ordering::Relaxed);
+        }
+        return Ok(());
+    }
+
+    /// 最近分配或使用的订单号
+    pub fn last(&self) -> u64 {
+        return self.last.load(Ordering::Relaxed);
+    }
+
+    /// 之后分配的订单号从last之后开始, 用于加入已有订单簿
+    pub fn observe(&self, last: u64) {
+        self.last.fetch_max(last, Ordering::Relaxed);
+    }
 }
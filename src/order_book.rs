@@ -1,13 +1,54 @@
-use std::cmp::min;
+use std::{cmp::min, collections::HashMap, sync::Arc};
 use rust_decimal::{prelude::Zero, Decimal};
+use serde::Serialize;
 
-use super::{queue::ResultQueue, Order, OrderQueue, OrderSide};
+use super::{queue::ResultQueue, CancelFilter, Clock, FeeSchedule, GroupKind, GroupLeg, Instrument, Lots, Order, OrderGroup, OrderIds, OrderKind, OrderRecord, OrderRef, OrderQueue, OrderSide, OrderStore, RiskLimits, RiskState, Scale, Symbol, SystemClock, Trade, TradeTape, TradingStatus};
+
+/// 盘口深度, (价格, 挂单量)
+#[derive(Clone, Debug, Serialize)]
+pub struct Depth {
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+/// 等待触发的止损单
+struct StopOrder {
+    order: Order,
+    triggered: bool,
+}
 
 pub struct OrderBook {
     pub symbol: String,
+    pub instrument: Instrument,
     pub buy_queue: OrderQueue,
     pub sell_queue: OrderQueue,
     pub result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    pub fee_schedule: FeeSchedule,
+    pub risk_state: RiskState,
+    /// 订单状态及成交明细
+    pub order_store: OrderStore,
+    /// 最近成交
+    pub trade_tape: TradeTape,
+    /// 受理及成交时间的时钟, 加入Engine时替换为Engine的时钟
+    pub clock: Arc<dyn Clock>,
+    /// 订单号分配器, 加入Engine时替换为Engine的分配器
+    pub(crate) order_ids: Arc<OrderIds>,
+    /// 价格及挂单量按创建时的tick_size及lot_size换算为tick及Lots
+    scale: Scale,
+    /// 挂钩单 (order_id, 方向), 按下单顺序
+    pegged: Vec<(u64, OrderSide)>,
+    /// 上次计算挂钩单价格时的参考价
+    peg_refs: (Option<Decimal>, Option<Decimal>),
+    /// 止损单, 按下单顺序
+    stops: Vec<StopOrder>,
+    /// 最新成交价
+    last_price: Option<Decimal>,
+    /// 订单组, group_id -> 订单组
+    groups: HashMap<u64, OrderGroup>,
+    /// 已生效的组内订单, order_id -> group_id
+    group_of: HashMap<u64, u64>,
+    /// 最近一笔成交的序号
+    last_trade_id: u64,
 }
 
 impl OrderBook {
@@ -15,75 +56,213 @@ impl OrderBook {
         symbol: String,
         result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
     ) -> Self {
+        return OrderBook::with_instrument(Instrument::new(&symbol), result_queue);
+    }
+
+    /// 按标的配置创建, 价位按tick_size索引, 之后修改tick_size不影响已创建的队列
+    pub fn with_instrument(
+        instrument: Instrument,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Self {
+        let scale = Scale::new(instrument.tick_size, instrument.lot_size);
+        // 之后按名称创建的订单共享驻留的名称
+        Symbol::intern(&instrument.symbol);
         let book = OrderBook {
-            symbol: symbol,
-            buy_queue: OrderQueue::new(OrderSide::Buy, false),
-            sell_queue: OrderQueue::new(OrderSide::Sell, true),
-            result_queue: result_queue,
+            symbol: instrument.symbol.clone(),
+            buy_queue: OrderQueue::new(OrderSide::Buy, scale),
+            sell_queue: OrderQueue::new(OrderSide::Sell, scale),
+            scale,
+            instrument,
+            result_queue,
+            fee_schedule: FeeSchedule::default(),
+            risk_state: RiskState::new(scale),
+            order_store: OrderStore::default(),
+            trade_tape: TradeTape::default(),
+            clock: Arc::new(SystemClock),
+            order_ids: Arc::new(OrderIds::default()),
+            pegged: Vec::new(),
+            peg_refs: (None, None),
+            stops: Vec::new(),
+            last_price: None,
+            groups: HashMap::new(),
+            group_of: HashMap::new(),
+            last_trade_id: 0,
         };
         return book;
     }
 
 
-    fn trade(&mut self, taker_order: &mut Order) -> bool {
+    /// 与对手盘第一笔可成交的订单成交一次, tick及lots为taker_order的价格及剩余数量
+    ///
+    /// 只修改lots, taker_order.volumn由调用方在撮合结束后按lots同步
+    ///
+    /// 对手盘价格不满足或者只有无法全部成交的挂单时返回true
+    fn trade(&mut self, taker_order: &mut Order, tick: i64, lots: &mut Lots) -> bool {
         // 返回true为 需要放入队列
-        let queue: &mut OrderQueue;
-        match taker_order.side {
-            OrderSide::Buy => {
-                // 获取最新卖价
-                let maker_price = self.sell_queue.first_price();
-                // 卖价大于买价 或者没有卖单 单子放入队列
-                if maker_price.is_none() || maker_price.unwrap() > taker_order.price {
-                    return true;
-                }
-                // 否则开始搓单
-                queue = &mut self.sell_queue;
-            }
-            OrderSide::Sell => {
-                // 获取最新买价
-                let maker_price = self.buy_queue.first_price();
-                // 买价低于买价 或者没有买单 单子放入队列
-                if maker_price.is_none() || maker_price.unwrap() < taker_order.price {
-                    return true;
-                }
-                queue = &mut self.buy_queue;
-                // 否则开始搓单
-            }
+        let queue: &mut OrderQueue = match taker_order.side {
+            OrderSide::Buy => &mut self.sell_queue,
+            OrderSide::Sell => &mut self.buy_queue,
         };
 
-        if let Some((maker_order_id, volumn, price)) = queue.first_order(|value| {
-            if let Some(maker_order) = value {
-                let volumn = min(maker_order.volumn, taker_order.volumn);
-                maker_order.volumn -= volumn;
-                taker_order.volumn -= volumn;
-                return Some((maker_order.id, volumn, maker_order.price));
-            };
-            return None;
-        }) {
-            if let Some(result_queue) = &self.result_queue {
-                result_queue.trade_success(maker_order_id, taker_order.id, volumn, price);
+        let fee_schedule = &self.fee_schedule;
+        let risk_state = &mut self.risk_state;
+        let scale = self.scale;
+        let taker_lots = *lots;
+        let (trade_id, timestamp) = (self.last_trade_id + 1, self.clock.now());
+        let mut maker_aon = false;
+        let trade = queue.match_order(tick, taker_lots, |maker_order, maker_lots| {
+            let filled = min(*maker_lots, *lots);
+            *maker_lots -= filled;
+            *lots -= filled;
+            // 剩余数量按Lots计算, taker的volumn在撮合结束后同步
+            let volumn = scale.volumn(filled);
+            maker_order.volumn = scale.volumn(*maker_lots);
+            risk_state.on_fill(maker_order, taker_order.owner, filled);
+            maker_aon = maker_order.all_or_none;
+            let price = maker_order.price;
+            return Trade {
+                trade_id,
+                maker_id: maker_order.id,
+                taker_id: taker_order.id,
+                maker_owner: maker_order.owner,
+                taker_owner: taker_order.owner,
+                maker_client_order_id: maker_order.client_order_id.clone(),
+                taker_client_order_id: taker_order.client_order_id.clone(),
+                taker_side: taker_order.side,
+                price,
+                volumn,
+                maker_fee: fee_schedule.maker_fee(maker_order.fee_tier, price, volumn),
+                taker_fee: fee_schedule.taker_fee(taker_order.fee_tier, price, volumn),
+                fee_asset: fee_schedule.asset,
+                timestamp,
             };
+        });
+        let trade = match trade {
+            Some(trade) => trade,
+            None => return true,
+        };
+        if let Some(result_queue) = &self.result_queue {
+            result_queue.trade_success(&trade);
+            if !maker_aon {
+                result_queue.depth_changed(queue.side, trade.price, -trade.volumn);
+            }
+        };
+        self.order_store.on_trade(&trade);
+        self.trade_tape.push(&trade);
+        self.last_trade_id = trade_id;
+        self.last_price = Some(trade.price);
+        if !self.group_of.is_empty() {
+            self.on_group_fill(trade.maker_id, trade.volumn);
+            self.on_group_fill(trade.taker_id, trade.volumn);
+        }
+        for stop_order in self.stops.iter_mut() {
+            let order = &mut stop_order.order;
+            if let Some(stop) = &mut order.stop {
+                stop_order.triggered |= stop.on_trade(order.side, trade.price);
+            }
         }
         return false;
     }
 
-    /// 查看是否存在当前订单号
+    /// 最新成交价
+    pub fn last_price(&self) -> Option<Decimal> {
+        return self.last_price;
+    }
+
+    /// 对手盘在tick价格内可以成交的数量
+    fn available(&self, side: OrderSide, tick: i64, lots: Lots) -> Lots {
+        return match side {
+            OrderSide::Buy => self.sell_queue.available(tick, lots),
+            OrderSide::Sell => self.buy_queue.available(tick, lots),
+        };
+    }
+
+    /// 查看是否存在当前订单号, 包括买卖两侧挂单 未触发的止损单及等待生效的子单
     pub fn exists_order(&self, order: &Order) -> bool {
-        match order.side {
-            OrderSide::Buy => self.buy_queue.exists_order_id(order.id),
-            OrderSide::Sell => self.sell_queue.exists_order_id(order.id),
+        return self.get_order(order.id).is_some()
+            || self.pending_orders().any(|v| v.id == order.id);
+    }
+
+    /// 查看账户是否存在相同客户订单号的未完成订单
+    pub fn exists_client_order(&self, order: &Order) -> bool {
+        let client_order_id = match &order.client_order_id {
+            Some(client_order_id) => client_order_id,
+            None => return false,
+        };
+        return self.get_client_order(order.owner, client_order_id).is_some()
+            || self
+                .pending_orders()
+                .any(|v| v.owner == order.owner && v.client_order_id.as_ref() == Some(client_order_id));
+    }
+
+    /// 等待父单成交的子单
+    fn pending_orders(&self) -> impl Iterator<Item = &Order> {
+        return self.groups.values().flat_map(|v| v.pending.iter());
+    }
+
+    /// 为订单号为0的订单分配订单号, 加入Engine后与Engine内的其他订单簿共用分配器
+    pub fn assign_order_id(&mut self, order: &mut Order) -> Result<(), &'static str> {
+        return self.order_ids.assign(order);
+    }
+
+    /// 改用Engine的订单号分配器, 之后分配的订单号从订单簿已使用的订单号之后开始
+    pub(crate) fn set_order_ids(&mut self, order_ids: Arc<OrderIds>) {
+        order_ids.observe(self.order_ids.last());
+        self.order_ids = order_ids;
+    }
+
+    /// 订单号已被使用, 包括已结束且仍在保留期内的订单
+    fn used_order_id(&self, order: &Order) -> bool {
+        return self.exists_order(order) || self.order_store.contains(order.id);
+    }
+
+    /// 检查价格数量能否换算为tick及Lots, 市价单不检查价格
+    ///
+    /// 在受理订单及撤出原订单前检查, 通过后撮合时的换算不会失败
+    fn check_scale(&self, order: &Order) -> Result<(), &'static str> {
+        if order.kind != OrderKind::Market {
+            self.scale.ticks(order.price).ok_or("invalid price")?;
+        }
+        for volumn in std::iter::once(order.volumn).chain(order.min_volumn) {
+            self.scale.lots(volumn).ok_or("invalid volumn")?;
         }
+        return Ok(());
+    }
+
+    /// 订单价格数量换算为tick及Lots
+    fn scale_order(&self, order: &Order) -> Result<(i64, Lots), &'static str> {
+        let tick = self.scale.ticks(order.price).ok_or("invalid price")?;
+        let lots = self.scale.lots(order.volumn).ok_or("invalid volumn")?;
+        return Ok((tick, lots));
     }
 
     /// 新建限价单
+    ///
+    /// 全部成交或不成交的订单不能全部成交时不成交, 直接挂单, 挂单价格可能与对手盘交叉.
+    /// 这类挂单不计入深度 最优价及挂钩参考价, 盘口中其余挂单保持不交叉
     pub fn limit(&mut self, mut taker_order: Order) -> Result<bool, &'static str> {
+        let (tick, mut lots) = self.scale_order(&taker_order)?;
+        let fillable = !taker_order.all_or_none
+            || self.available(taker_order.side, tick, lots) >= lots;
         loop {
-            if self.trade(&mut taker_order) {
-                return match taker_order.side {
-                    OrderSide::Buy => self.buy_queue.push(taker_order),
-                    OrderSide::Sell => self.sell_queue.push(taker_order),
+            if !fillable || self.trade(&mut taker_order, tick, &mut lots) {
+                let queue = match taker_order.side {
+                    OrderSide::Buy => &mut self.buy_queue,
+                    OrderSide::Sell => &mut self.sell_queue,
                 };
-            } else if taker_order.volumn.is_zero() {
+                if queue.exists_order_id(taker_order.id) {
+                    return Err("duplicate order_id");
+                }
+                taker_order.volumn = self.scale.volumn(lots);
+                self.risk_state.on_rest(&taker_order);
+                let (side, price, volumn) = (taker_order.side, taker_order.price, taker_order.volumn);
+                let aon = taker_order.all_or_none;
+                queue.push(taker_order)?;
+                if !aon {
+                    self.depth_changed(side, price, volumn);
+                }
+                return Ok(true);
+            } else if lots.is_zero() {
                 return Ok(true);
             }
         }
@@ -97,9 +276,7 @@ impl OrderBook {
                     order.price = first_price;
                     return self.limit(order);
                 } else {
-                    if let Some(result_queue) = &self.result_queue {
-                        result_queue.cancel_order(order.id, order.volumn)
-                    }
+                    self.cancelled(&order);
                     return Err("empty sell queue");
                 }
             }
@@ -108,67 +285,248 @@ impl OrderBook {
                     order.price = first_price;
                     return self.limit(order);
                 } else {
-                    if let Some(result_queue) = &self.result_queue {
-                        result_queue.cancel_order(order.id, order.volumn)
-                    }
+                    self.cancelled(&order);
                     return Err("empty buy queue");
                 }
             }
         }
     }
 
-    /// 新建IOC单
+    /// 新建IOC单, 在限价内尽量成交, 剩余部分撤销
+    ///
+    /// 设置了最小成交量时, 可成交数量不足则整单拒绝
     pub fn ioc(&mut self, mut taker_order: Order) -> Result<bool, &'static str> {
-        self.trade(&mut taker_order);
-        if !taker_order.volumn.is_zero() {
-            if let Some(result_queue) = &self.result_queue {
-                result_queue.cancel_order(taker_order.id, taker_order.volumn)
-            };
+        let (tick, mut lots) = self.scale_order(&taker_order)?;
+        if let Some(min_volumn) = taker_order.min_volumn {
+            let min_lots = self.scale.lots(min_volumn).ok_or("invalid volumn")?;
+            if self.available(taker_order.side, tick, lots) < min_lots {
+                return self.reject(&taker_order, "min volumn cannot be filled");
+            }
+        }
+        while !lots.is_zero() && !self.trade(&mut taker_order, tick, &mut lots) {}
+        if !lots.is_zero() {
+            taker_order.volumn = self.scale.volumn(lots);
+            self.cancelled(&taker_order);
         }
         Ok(true)
     }
     
-    /// 新建FOK单
+    /// 新建FOK单, 限价内对手盘数量足够时全部成交, 否则拒绝
     pub fn fok(&mut self, taker_order: Order) -> Result<bool, &'static str> {
-        let less;
-        match taker_order.side {
-            OrderSide::Buy => {
-                less = false;
-            }
-            OrderSide::Sell => {
-                less = true;
+        let (tick, lots) = self.scale_order(&taker_order)?;
+        if self.available(taker_order.side, tick, lots) < lots {
+            return self.reject(&taker_order, "fok order cannot be filled");
+        }
+        return self.limit(taker_order);
+    }
+
+    /// 修改标的名称, 挂单 未触发的止损单及订单组待提交的子单同步修改
+    pub fn rename(&mut self, symbol: &str) {
+        self.symbol = symbol.to_string();
+        self.instrument.symbol = symbol.to_string();
+        let symbol = Symbol::intern(symbol);
+        self.buy_queue.set_symbol(&symbol);
+        self.sell_queue.set_symbol(&symbol);
+        for stop_order in self.stops.iter_mut() {
+            stop_order.order.symbol = symbol.clone();
+        }
+        for order in self.groups.values_mut().flat_map(|v| v.pending.iter_mut()) {
+            order.symbol = symbol.clone();
+        }
+    }
+
+    /// 查看盘口深度
+    pub fn depth(&self, limit: usize) -> Depth {
+        Depth {
+            bids: self.buy_queue.depth(limit),
+            asks: self.sell_queue.depth(limit),
+        }
+    }
+
+    /// 查看挂单, 包括未触发的止损单
+    pub fn get_order(&self, order_id: u64) -> Option<&Order> {
+        self.buy_queue
+            .get(order_id)
+            .or_else(|| self.sell_queue.get(order_id))
+            .or_else(|| {
+                self.stops
+                    .iter()
+                    .map(|v| &v.order)
+                    .find(|v| v.id == order_id)
+            })
+    }
+
+    /// 按账户及客户订单号查看挂单, 包括未触发的止损单
+    pub fn get_client_order(&self, owner: u64, client_order_id: &str) -> Option<&Order> {
+        let order_id = self.order_store.client_order(owner, client_order_id)?;
+        return self.get_order(order_id).filter(|v| v.owner == owner);
+    }
+
+    /// 按订单号或客户订单号查看挂单
+    pub fn find_order(&self, order_ref: &OrderRef) -> Option<&Order> {
+        return match order_ref {
+            OrderRef::Id(order_id) => self.get_order(*order_id),
+            OrderRef::Client { owner, client_order_id } => self.get_client_order(*owner, client_order_id),
+        };
+    }
+
+    /// 按订单号或客户订单号查看订单状态及成交明细, 包括已结束且仍在保留期内的订单
+    ///
+    /// 未触发的止损单返回当前触发价及追踪价位
+    pub fn order_status(&self, order_ref: &OrderRef) -> Option<OrderRecord> {
+        let order_id = match order_ref {
+            OrderRef::Id(order_id) => *order_id,
+            OrderRef::Client { owner, client_order_id } => {
+                self.order_store.client_order(*owner, client_order_id)?
             }
         };
+        let mut record = self.order_store.get(order_id)?;
+        if !record.status.is_terminal() {
+            if let Some(stop_order) = self.stops.iter().find(|v| v.order.id == order_id) {
+                record.order.stop = stop_order.order.stop;
+            }
+        }
+        return Some(record);
+    }
 
-        let mut volumn = Decimal::zero();
-        'outer: for queue in &self.sell_queue.list {
-            for maker_order in &queue.list {
-                if volumn >= taker_order.volumn {
-                    break 'outer;
-                } else if (less && maker_order.price < taker_order.price)
-                    || (!less && maker_order.price > taker_order.price)
-                {
-                    break 'outer;
-                } else {
-                    volumn += maker_order.volumn;
+    /// 查看账户挂单
+    pub fn open_orders(&self, owner: u64) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for queue in [&self.buy_queue, &self.sell_queue] {
+            for (order_id, _) in queue.select(Some(owner), None, None) {
+                if let Some(order) = queue.get(order_id) {
+                    orders.push(order.clone());
                 }
             }
         }
-        if volumn >= taker_order.volumn {
-            return self.limit(taker_order);
-        } else {
-            if let Some(result_queue) = &self.result_queue {
-                result_queue.cancel_order(taker_order.id, taker_order.volumn)
-            };
-            return Ok(true);
+        for stop_order in self.stops.iter() {
+            if stop_order.order.owner == owner {
+                orders.push(stop_order.order.clone());
+            }
         }
+        return orders;
     }
 
-    /// 新建订单通过订单kind判断 下单类型
-    pub fn add_order(&mut self, order: Order) -> Result<bool, &'static str> {
-        if self.exists_order(&order) {
-            return Err("duplicate order_id");
+    /// 风控检查, 市价单按对手盘最优价计算金额
+    pub fn check_risk(&self, order: &Order, limits: &RiskLimits) -> Result<(), &'static str> {
+        let exposure = self
+            .risk_state
+            .exposure(order.owner)
+            .unwrap_or_default();
+        return limits.check(order, self.risk_price(order), &exposure);
+    }
+
+    /// 按改单后的价格数量检查风控, 不计该订单改单前的挂单
+    pub fn check_amend_risk(
+        &self,
+        order_id: u64,
+        new_price: Decimal,
+        new_volumn: Decimal,
+        limits: &RiskLimits,
+    ) -> Result<(), &'static str> {
+        let order = self.get_order(order_id).ok_or("not found order")?;
+        let mut exposure = self
+            .risk_state
+            .exposure(order.owner)
+            .unwrap_or_default();
+        // 未触发的止损单不在挂单队列, 不计入敞口
+        if self.buy_queue.get(order_id).or_else(|| self.sell_queue.get(order_id)).is_some() {
+            exposure.open_orders -= 1;
+            match order.side {
+                OrderSide::Buy => exposure.open_buy -= order.volumn,
+                OrderSide::Sell => exposure.open_sell -= order.volumn,
+            }
         }
+        let mut order = order.clone();
+        order.price = new_price;
+        order.volumn = new_volumn;
+        return limits.check(&order, self.risk_price(&order), &exposure);
+    }
+
+    /// 风控参考成交价, 市价单取对手方最优价
+    fn risk_price(&self, order: &Order) -> Decimal {
+        return match order.kind {
+            OrderKind::Market => match order.side {
+                OrderSide::Buy => self.sell_queue.first_price(),
+                OrderSide::Sell => self.buy_queue.first_price(),
+            }
+            .unwrap_or_else(Decimal::zero),
+            _ => order.price,
+        };
+    }
+
+    /// 新建订单通过订单kind判断 下单类型, 返回订单号
+    pub fn add_order(&mut self, mut order: Order) -> Result<u64, &'static str> {
+        self.assign_order_id(&mut order)?;
+        let order_id = order.id;
+        let res = self.place_order(order);
+        self.on_book_changed();
+        return res.map(|_| order_id);
+    }
+
+    fn place_order(&mut self, mut order: Order) -> Result<bool, &'static str> {
+        if self.used_order_id(&order) {
+            return self.reject(&order, "duplicate order_id");
+        }
+        if self.exists_client_order(&order) {
+            return self.reject(&order, "duplicate client_order_id");
+        }
+        if order.all_or_none && order.kind != OrderKind::Limit {
+            return self.reject(&order, "all-or-none requires limit order");
+        }
+        if order.min_volumn.is_some() && order.kind != OrderKind::IOC {
+            return self.reject(&order, "min volumn requires ioc order");
+        }
+        if let Some(stop) = &order.stop {
+            if !matches!(order.kind, OrderKind::Limit | OrderKind::Market) {
+                return self.reject(&order, "stop requires limit or market order");
+            }
+            if order.peg.is_some() {
+                return self.reject(&order, "stop order cannot be pegged");
+            }
+            if let Err(e) = stop.check() {
+                return self.reject(&order, e);
+            }
+        }
+        if let Some(peg) = order.peg {
+            if order.kind != OrderKind::Limit {
+                return self.reject(&order, "peg requires limit order");
+            }
+            // 偏移及价格限制须为tick_size整数倍, 否则重新定价后的价格无法挂单
+            let valid_limit = peg
+                .limit
+                .is_none_or(|v| v > Decimal::zero() && self.scale.ticks(v).is_some());
+            if self.scale.ticks(peg.offset).is_none() || !valid_limit {
+                return self.reject(&order, "invalid peg");
+            }
+            if let Err(e) = self.price_peg(&mut order) {
+                return self.reject(&order, e);
+            }
+        }
+        if let Err(e) = self.check_scale(&order) {
+            return self.reject(&order, e);
+        }
+        if order.peg.is_some() {
+            self.pegged.push((order.id, order.side));
+        }
+        order.accepted_at = Some(self.clock.now());
+        self.order_store.on_accepted(&order);
+        if let Some(result_queue) = &self.result_queue {
+            result_queue.order_accepted(&order);
+        }
+        let side = order.side;
+        if let Some(stop) = &mut order.stop {
+            let triggered = self
+                .last_price
+                .is_some_and(|price| stop.on_trade(side, price));
+            self.stops.push(StopOrder { order, triggered });
+            return Ok(true);
+        }
+        return self.execute(order);
+    }
+
+    /// 按订单类型撮合
+    fn execute(&mut self, order: Order) -> Result<bool, &'static str> {
         return match order.kind {
             super::OrderKind::Limit => self.limit(order),
             super::OrderKind::Market => self.market(order),
@@ -177,6 +535,140 @@ impl OrderBook {
         };
     }
 
+    /// 拒绝订单并通知结果队列, 与已有订单的订单号重复时不修改该订单的记录
+    pub fn reject(&mut self, order: &Order, reason: &'static str) -> Result<bool, &'static str> {
+        if !self.exists_order(order) {
+            self.order_store.on_rejected(order, reason);
+        }
+        if let Some(result_queue) = &self.result_queue {
+            result_queue.order_rejected(order, reason);
+        }
+        return Err(reason);
+    }
+
+    /// 改单, 价格不变且只减少数量时保留队列优先级, 否则撤单后按新价格数量重新下单
+    ///
+    /// 挂钩单只能修改数量, 未触发的止损单直接修改价格数量
+    pub fn amend_order(
+        &mut self,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let res = self.amend(order_id, price, side, new_price, new_volumn);
+        self.on_book_changed();
+        return res;
+    }
+
+    fn amend(
+        &mut self,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        if self.instrument.status != TradingStatus::Trading {
+            return Err("trading halted");
+        }
+        let kind = self.get_order(order_id).map_or(OrderKind::Limit, |v| v.kind);
+        self.instrument.check_precision(kind, new_price, new_volumn)?;
+        if kind != OrderKind::Market {
+            self.scale.ticks(new_price).ok_or("invalid price")?;
+        }
+        self.scale.lots(new_volumn).ok_or("invalid volumn")?;
+        if new_price != price && self.get_order(order_id).is_some_and(|v| v.peg.is_some()) {
+            return Err("cannot amend pegged order price");
+        }
+        // 未触发的止损单直接修改, 触发条件不变
+        let stop_order = self.stops.iter_mut().find(|v| {
+            return v.order.id == order_id && v.order.side == side && v.order.price == price;
+        });
+        if let Some(stop_order) = stop_order {
+            stop_order.order.price = new_price;
+            stop_order.order.volumn = new_volumn;
+            let order = stop_order.order.clone();
+            self.amend_group_leg(order_id, new_volumn);
+            self.amended(&order);
+            return Ok(true);
+        }
+        let queue = match side {
+            OrderSide::Buy => &mut self.buy_queue,
+            OrderSide::Sell => &mut self.sell_queue,
+        };
+        if new_price == price {
+            if let Some((old_volumn, order)) = queue.reduce_volumn(price, order_id, new_volumn) {
+                self.risk_state.on_reduce(&order, old_volumn - new_volumn);
+                if !order.all_or_none {
+                    self.depth_changed(side, price, new_volumn - old_volumn);
+                }
+                self.amend_group_leg(order_id, new_volumn);
+                self.amended(&order);
+                return Ok(true);
+            }
+        }
+        let mut order = match self.remove_order(order_id, price, side) {
+            Some(order) => order,
+            None => return Err("not found order"),
+        };
+        self.amend_group_leg(order_id, new_volumn);
+        order.price = new_price;
+        order.volumn = new_volumn;
+        self.amended(&order);
+        return self.limit(order);
+    }
+
+    /// 按订单号或客户订单号改单
+    pub fn amend_by_ref(
+        &mut self,
+        order_ref: &OrderRef,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let (order_id, price, side) = self
+            .find_order(order_ref)
+            .map(|v| (v.id, v.price, v.side))
+            .ok_or("not found order")?;
+        return self.amend_order(order_id, price, side, new_price, new_volumn);
+    }
+
+    /// 批量撤单, 按账户 方向 价格区间筛选
+    pub fn mass_cancel(&mut self, filter: &CancelFilter) -> Vec<Order> {
+        let mut orders = Vec::new();
+        let matches = |order: &Order| {
+            return filter.owner.is_none_or(|v| v == order.owner)
+                && filter.side.is_none_or(|v| v == order.side)
+                && filter.min_price.is_none_or(|v| order.price >= v)
+                && filter.max_price.is_none_or(|v| order.price <= v);
+        };
+        let (cancelled, stops) = std::mem::take(&mut self.stops)
+            .into_iter()
+            .partition(|v| matches(&v.order));
+        self.stops = stops;
+        for stop_order in cancelled {
+            self.cancelled(&stop_order.order);
+            orders.push(stop_order.order);
+        }
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            if filter.side.is_some_and(|v| v != side) {
+                continue;
+            }
+            let queue = match side {
+                OrderSide::Buy => &self.buy_queue,
+                OrderSide::Sell => &self.sell_queue,
+            };
+            for (order_id, price) in queue.select(filter.owner, filter.min_price, filter.max_price) {
+                if let Some(order) = self.cancel(order_id, price, side) {
+                    orders.push(order);
+                }
+            }
+        }
+        self.on_book_changed();
+        return orders;
+    }
+
     /// 撤销订单
     pub fn calcen_order(
         &mut self,
@@ -184,9 +676,367 @@ impl OrderBook {
         price: Decimal,
         side: OrderSide,
     ) -> Option<Order> {
-        return match side {
+        let order = self.cancel(order_id, price, side);
+        self.on_book_changed();
+        return order;
+    }
+
+    /// 按订单号或客户订单号撤单
+    pub fn cancel_by_ref(&mut self, order_ref: &OrderRef) -> Option<Order> {
+        let (order_id, price, side) = self.find_order(order_ref).map(|v| (v.id, v.price, v.side))?;
+        return self.calcen_order(order_id, price, side);
+    }
+
+    fn cancel(&mut self, order_id: u64, price: Decimal, side: OrderSide) -> Option<Order> {
+        let index = self.stops.iter().position(|v| {
+            return v.order.id == order_id && v.order.side == side && v.order.price == price;
+        });
+        let order = match index {
+            Some(index) => Some(self.stops.remove(index).order),
+            None => self.remove_order(order_id, price, side),
+        };
+        if let Some(order) = &order {
+            self.cancelled(order);
+        }
+        return order;
+    }
+
+    /// 撮合及撤单后重新计算挂钩单价格, 处理触发的止损单及订单组, 直到不再变化
+    fn on_book_changed(&mut self) {
+        loop {
+            self.reprice_pegs();
+            let triggered = self.trigger_stops();
+            if !self.settle_groups() && !triggered {
+                return;
+            }
+        }
+    }
+
+    /// 新建OCO订单组, 一笔订单成交或撤销后撤销其余订单, 返回group_id
+    ///
+    /// 按顺序下单, 有订单立即成交时其余订单不再下单并通知撤销
+    pub fn add_oco(&mut self, mut orders: Vec<Order>) -> Result<u64, &'static str> {
+        if orders.len() < 2 {
+            return Err("oco requires at least two orders");
+        }
+        for order in orders.iter_mut() {
+            self.assign_order_id(order)?;
+        }
+        let group_id = self.add_group(GroupKind::Oco, &orders, Vec::new())?;
+        self.place_legs(group_id, orders);
+        self.on_book_changed();
+        return Ok(group_id);
+    }
+
+    /// 新建OTO订单组, 父单全部成交后子单生效, 多个子单组成OCO, 返回group_id
+    ///
+    /// 父单未全部成交即撤销时子单一并撤销
+    pub fn add_oto(&mut self, mut parent: Order, mut children: Vec<Order>) -> Result<u64, &'static str> {
+        if children.is_empty() {
+            return Err("oto requires child orders");
+        }
+        for order in std::iter::once(&mut parent).chain(children.iter_mut()) {
+            self.assign_order_id(order)?;
+        }
+        let mut all = children.clone();
+        all.push(parent.clone());
+        self.check_group(&all)?;
+        let group_id = self.add_group(GroupKind::Oto, std::slice::from_ref(&parent), children)?;
+        self.place_legs(group_id, vec![parent]);
+        self.on_book_changed();
+        return Ok(group_id);
+    }
+
+    /// 查看订单组
+    pub fn group(&self, group_id: u64) -> Option<&OrderGroup> {
+        return self.groups.get(&group_id);
+    }
+
+    /// 组内订单号及客户订单号不能重复, 也不能与已有订单重复
+    fn check_group(&self, orders: &[Order]) -> Result<(), &'static str> {
+        for (index, order) in orders.iter().enumerate() {
+            if self.used_order_id(order)
+                || self.groups.contains_key(&order.id)
+                || orders[..index].iter().any(|v| v.id == order.id)
+            {
+                return Err("duplicate order_id");
+            }
+            if self.exists_client_order(order)
+                || orders[..index].iter().any(|v| {
+                    v.owner == order.owner
+                        && v.client_order_id.is_some()
+                        && v.client_order_id == order.client_order_id
+                })
+            {
+                return Err("duplicate client_order_id");
+            }
+        }
+        return Ok(());
+    }
+
+    fn add_group(&mut self, kind: GroupKind, legs: &[Order], mut pending: Vec<Order>) -> Result<u64, &'static str> {
+        self.check_group(legs)?;
+        let group_id = legs[0].id;
+        for order in pending.iter_mut() {
+            order.group_id = Some(group_id);
+        }
+        self.groups
+            .insert(group_id, OrderGroup::new(group_id, kind, legs, pending));
+        for order in legs {
+            self.group_of.insert(order.id, group_id);
+        }
+        return Ok(group_id);
+    }
+
+    /// 按顺序下单, 已下的订单有成交或已结束时停止, 未下的订单通知撤销并移出订单组
+    fn place_legs(&mut self, group_id: u64, orders: Vec<Order>) {
+        let mut placed = Vec::new();
+        let mut orders = orders.into_iter();
+        for mut order in orders.by_ref() {
+            order.group_id = Some(group_id);
+            placed.push(order.id);
+            let _ = self.place_order(order);
+            let done = match self.groups.get(&group_id) {
+                Some(group) => group.legs.iter().any(|v| {
+                    placed.contains(&v.order_id)
+                        && (!v.filled.is_zero() || self.get_order(v.order_id).is_none())
+                }),
+                None => true,
+            };
+            if done {
+                break;
+            }
+        }
+        for mut order in orders {
+            order.group_id = Some(group_id);
+            self.group_of.remove(&order.id);
+            if let Some(group) = self.groups.get_mut(&group_id) {
+                group.legs.retain(|v| v.order_id != order.id);
+            }
+            self.cancelled(&order);
+        }
+    }
+
+    fn group_leg_mut(&mut self, order_id: u64) -> Option<&mut GroupLeg> {
+        let group_id = *self.group_of.get(&order_id)?;
+        return self.groups.get_mut(&group_id)?.leg_mut(order_id);
+    }
+
+    /// 改单成功后更新订单组中该订单的数量
+    fn amend_group_leg(&mut self, order_id: u64, new_volumn: Decimal) {
+        if let Some(leg) = self.group_leg_mut(order_id) {
+            leg.volumn = leg.filled + new_volumn;
+        }
+    }
+
+    fn on_group_fill(&mut self, order_id: u64, volumn: Decimal) {
+        if let Some(leg) = self.group_leg_mut(order_id) {
+            leg.filled += volumn;
+        }
+    }
+
+    fn remove_group(&mut self, group_id: u64) -> Option<OrderGroup> {
+        let group = self.groups.remove(&group_id)?;
+        for leg in group.legs.iter() {
+            self.group_of.remove(&leg.order_id);
+        }
+        return Some(group);
+    }
+
+    /// 按group_id顺序处理订单组, 返回是否有订单组结束
+    ///
+    /// OCO有订单成交或已结束时撤销其余未成交的订单.
+    /// OTO父单结束后, 全部成交则子单下单, 否则子单通知撤销
+    fn settle_groups(&mut self) -> bool {
+        if self.groups.is_empty() {
+            return false;
+        }
+        let mut group_ids: Vec<u64> = self.groups.keys().copied().collect();
+        group_ids.sort_unstable();
+        let mut settled = false;
+        for group_id in group_ids {
+            let group = match self.groups.get(&group_id) {
+                Some(group) => group,
+                None => continue,
+            };
+            let open = |order_id: u64| self.get_order(order_id).is_some();
+            let done = match group.kind {
+                GroupKind::Oco => group
+                    .legs
+                    .iter()
+                    .any(|v| !v.filled.is_zero() || !open(v.order_id)),
+                GroupKind::Oto => !open(group.legs[0].order_id),
+            };
+            if !done {
+                continue;
+            }
+            settled = true;
+            let group = self.remove_group(group_id).unwrap();
+            match group.kind {
+                GroupKind::Oco => {
+                    for leg in group.legs.iter().filter(|v| v.filled.is_zero()) {
+                        let order = self.get_order(leg.order_id).map(|v| (v.price, v.side));
+                        if let Some((price, side)) = order {
+                            self.cancel(leg.order_id, price, side);
+                        }
+                    }
+                }
+                GroupKind::Oto => {
+                    let parent = &group.legs[0];
+                    if parent.filled >= parent.volumn {
+                        let children = group.pending;
+                        if children.len() > 1 {
+                            self.groups.insert(
+                                group_id,
+                                OrderGroup::new(group_id, GroupKind::Oco, &children, Vec::new()),
+                            );
+                            for order in children.iter() {
+                                self.group_of.insert(order.id, group_id);
+                            }
+                            self.place_legs(group_id, children);
+                        } else {
+                            for order in children {
+                                let _ = self.place_order(order);
+                            }
+                        }
+                    } else {
+                        for order in group.pending.iter() {
+                            self.cancelled(order);
+                        }
+                    }
+                }
+            }
+        }
+        return settled;
+    }
+
+    /// 按下单顺序提交已触发的止损单, 返回是否有止损单触发
+    fn trigger_stops(&mut self) -> bool {
+        if !self.stops.iter().any(|v| v.triggered) {
+            return false;
+        }
+        let (triggered, stops): (Vec<StopOrder>, Vec<StopOrder>) = std::mem::take(&mut self.stops)
+            .into_iter()
+            .partition(|v| v.triggered);
+        self.stops = stops;
+        for stop_order in triggered {
+            let mut order = stop_order.order;
+            order.stop = None;
+            let _ = self.execute(order);
+        }
+        return true;
+    }
+
+    /// 不含挂钩单的买一卖一价
+    fn peg_refs(&self) -> (Option<Decimal>, Option<Decimal>) {
+        return (
+            self.buy_queue.first_reference_price(),
+            self.sell_queue.first_reference_price(),
+        );
+    }
+
+    /// 按当前盘口计算挂钩单价格, 非挂钩单不变
+    pub fn price_peg(&self, order: &mut Order) -> Result<(), &'static str> {
+        if let Some(peg) = order.peg {
+            let (bid, ask) = self.peg_refs();
+            order.price = peg
+                .price(order.side, bid, ask, self.scale.tick_size)
+                .ok_or("peg reference price unavailable")?;
+        }
+        return Ok(());
+    }
+
+    /// 参考价变化后按下单顺序重新计算挂钩单价格, 直到参考价不再变化
+    ///
+    /// 参考价不含挂钩单, 避免挂钩单互相追价. 价格不变的挂钩单保留队列位置,
+    /// 价格变化的撤出后按新价格重新下单, 排在新价位末尾并可能立即成交,
+    /// 之后回到原价格也不恢复原来的优先级. 参考价缺失时保持原价格
+    fn reprice_pegs(&mut self) {
+        if self.pegged.is_empty() {
+            return;
+        }
+        loop {
+            let (bid, ask) = self.peg_refs();
+            if (bid, ask) == self.peg_refs {
+                return;
+            }
+            self.peg_refs = (bid, ask);
+            let pegged = std::mem::take(&mut self.pegged);
+            for (order_id, side) in pegged.iter().copied() {
+                let queue = match side {
+                    OrderSide::Buy => &self.buy_queue,
+                    OrderSide::Sell => &self.sell_queue,
+                };
+                let (price, new_price) = match queue.get(order_id) {
+                    Some(order) => (
+                        order.price,
+                        order
+                            .peg
+                            .and_then(|v| v.price(side, bid, ask, self.scale.tick_size)),
+                    ),
+                    None => continue,
+                };
+                let new_price = match new_price {
+                    Some(new_price) if new_price != price => new_price,
+                    _ => continue,
+                };
+                if let Some(mut order) = self.remove_order(order_id, price, side) {
+                    order.price = new_price;
+                    // 新价格无法换算时撤销该订单
+                    if self.scale_order(&order).is_err() {
+                        order.price = price;
+                        self.cancelled(&order);
+                        continue;
+                    }
+                    self.amended(&order);
+                    // 价格数量已检查, 重新下单不会失败
+                    let _ = self.limit(order);
+                }
+            }
+            self.pegged = pegged;
+            let (buy_queue, sell_queue) = (&self.buy_queue, &self.sell_queue);
+            self.pegged.retain(|(order_id, side)| match side {
+                OrderSide::Buy => buy_queue.exists_order_id(*order_id),
+                OrderSide::Sell => sell_queue.exists_order_id(*order_id),
+            });
+        }
+    }
+
+    fn remove_order(&mut self, order_id: u64, price: Decimal, side: OrderSide) -> Option<Order> {
+        let order = match side {
             super::OrderSide::Buy => self.buy_queue.remove(price, order_id),
             super::OrderSide::Sell => self.sell_queue.remove(price, order_id),
         };
+        if let Some(order) = &order {
+            self.risk_state.on_remove(order);
+            if !order.all_or_none {
+                self.depth_changed(side, order.price, -order.volumn);
+            }
+        }
+        return order;
+    }
+
+    /// 记录撤单并通知结果队列, order.volumn为撤销数量
+    fn cancelled(&mut self, order: &Order) {
+        self.order_store.on_cancel(order);
+        if let Some(result_queue) = &self.result_queue {
+            result_queue.cancel_order(order);
+        }
+    }
+
+    /// 记录改单并通知结果队列
+    fn amended(&mut self, order: &Order) {
+        self.order_store.on_amended(order);
+        if let Some(result_queue) = &self.result_queue {
+            result_queue.order_amended(order);
+        }
+    }
+
+    /// 通知深度变化, 全部成交或不成交的挂单不计入深度, 其变化不通知
+    fn depth_changed(&self, side: OrderSide, price: Decimal, volumn: Decimal) {
+        if let Some(result_queue) = &self.result_queue {
+            result_queue.depth_changed(side, price, volumn);
+        }
     }
 }
+
@@ -1,188 +1,400 @@
-use std::collections::HashMap;
+use std::{
+    cmp::min,
+    collections::{BTreeMap, HashMap, HashSet},
+};
 
-use super::{Order, OrderSide};
+use super::{Lots, Order, OrderSide, Scale, Symbol};
 use rust_decimal::{prelude::Zero, Decimal};
-use skiplist::OrderedSkipList;
+use slab::Slab;
+
+/// 挂单节点, 同一价位的订单按时间先后组成双向链表
+struct OrderNode {
+    order: Order,
+    /// 与order.volumn一致
+    lots: Lots,
+    level: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// 价位, 缓存订单数及挂单量
+struct PriceLevel {
+    price: Decimal,
+    tick: i64,
+    head: Option<usize>,
+    tail: Option<usize>,
+    len: usize,
+    lots: Lots,
+    /// 全部成交或不成交的订单数
+    aon: usize,
+    /// 全部成交或不成交的挂单量, 不计入深度
+    aon_lots: Lots,
+    /// 挂钩单及全部成交或不成交的订单数, 不作为挂钩参考价
+    unreferenced: usize,
+}
 
 pub struct OrderQueue {
     pub side: OrderSide,
-    pub list: OrderedSkipList<OrderQueueItem>,
-    order_ids: HashMap<u64, ()>,
+    scale: Scale,
+    /// 价格tick数 -> 价位下标
+    ticks: BTreeMap<i64, usize>,
+    levels: Slab<PriceLevel>,
+    orders: Slab<OrderNode>,
+    /// order_id -> 订单下标
+    order_ids: HashMap<u64, usize>,
+    /// 账户挂单索引
+    owner_orders: HashMap<u64, HashSet<u64>>,
 }
 
 impl OrderQueue {
-    pub fn new(side: OrderSide, desc: bool) -> OrderQueue {
-        // todo 添加队列
+    /// 价格按tick_size换算为整数作为价位索引
+    pub fn new(side: OrderSide, scale: Scale) -> OrderQueue {
         OrderQueue {
-            side: side,
+            side,
+            scale,
+            ticks: BTreeMap::new(),
+            levels: Slab::new(),
+            orders: Slab::new(),
             order_ids: HashMap::new(),
-            list: unsafe {
-                OrderedSkipList::with_comp(move |a: &OrderQueueItem, b: &OrderQueueItem| {
-                    let read_a = a;
-                    let read_b = b;
-                    if desc {
-                        // 倒序
-                        return read_a
-                            .price
-                            .partial_cmp(&read_b.price)
-                            .expect("Element cannot be ordered.");
-                    } else {
-                        // 正序
-                        return (-read_a.price)
-                            .partial_cmp(&-read_b.price)
-                            .expect("Element cannot be ordered.");
-                    }
-                })
-            },
+            owner_orders: HashMap::new(),
         }
     }
+
+    /// 最优价位下标, 买盘最高价, 卖盘最低价
+    fn best_level(&self) -> Option<usize> {
+        let best = match self.side {
+            OrderSide::Buy => self.ticks.values().next_back(),
+            OrderSide::Sell => self.ticks.values().next(),
+        };
+        return best.copied();
+    }
+
+    /// 按价格优先顺序遍历价位
+    fn sorted_levels(&self) -> impl Iterator<Item = &PriceLevel> {
+        let (asc, desc) = match self.side {
+            OrderSide::Buy => (None, Some(self.ticks.values().rev())),
+            OrderSide::Sell => (Some(self.ticks.values()), None),
+        };
+        return asc
+            .into_iter()
+            .flatten()
+            .chain(desc.into_iter().flatten())
+            .map(move |v| &self.levels[*v]);
+    }
+
+    /// 价格不劣于tick的价位下标, 按价格优先顺序
+    fn levels_within(&self, tick: i64) -> impl Iterator<Item = usize> + '_ {
+        let (asc, desc) = match self.side {
+            OrderSide::Buy => (None, Some(self.ticks.range(tick..).rev())),
+            OrderSide::Sell => (Some(self.ticks.range(..=tick)), None),
+        };
+        return asc
+            .into_iter()
+            .flatten()
+            .chain(desc.into_iter().flatten())
+            .map(|(_, v)| *v);
+    }
+
+    /// 按时间优先顺序遍历价位内的订单节点
+    fn level_nodes<'a>(&'a self, level: &PriceLevel) -> impl Iterator<Item = (usize, &'a OrderNode)> {
+        let mut next = level.head;
+        return std::iter::from_fn(move || {
+            let index = next?;
+            let node = &self.orders[index];
+            next = node.next;
+            return Some((index, node));
+        });
+    }
+
+    /// 按时间优先顺序遍历价位内的订单
+    fn level_orders<'a>(&'a self, level: &PriceLevel) -> impl Iterator<Item = &'a Order> {
+        return self.level_nodes(level).map(|(_, node)| &node.order);
+    }
+
     pub fn push(&mut self, order: Order) -> Result<bool, &'static str> {
         if self.order_ids.contains_key(&order.id) {
             return Err("duplicate order_id");
         }
-        self.order_ids.insert(order.id, ());
-        let price = order.price;
-        let mut q = OrderQueueItem::new(price);
-        if let Some(mut queue) = self.list.remove_first(&q) {
-            queue.push(order);
-            self.list.insert(queue);
-        } else {
-            q.push(order);
-            self.list.insert(q);
+        let tick = match self.scale.ticks(order.price) {
+            Some(tick) => tick,
+            None => return Err("invalid price"),
+        };
+        let lots = match self.scale.lots(order.volumn) {
+            Some(lots) => lots,
+            None => return Err("invalid volumn"),
+        };
+        let level = match self.ticks.get(&tick) {
+            Some(level) => *level,
+            None => {
+                let level = self.levels.insert(PriceLevel {
+                    price: order.price,
+                    tick,
+                    head: None,
+                    tail: None,
+                    len: 0,
+                    lots: Lots::zero(),
+                    aon: 0,
+                    aon_lots: Lots::zero(),
+                    unreferenced: 0,
+                });
+                self.ticks.insert(tick, level);
+                level
+            }
+        };
+        let (order_id, owner, aon) = (order.id, order.owner, order.all_or_none);
+        let unreferenced = aon || order.peg.is_some();
+        let tail = self.levels[level].tail;
+        let index = self.orders.insert(OrderNode {
+            order,
+            lots,
+            level,
+            prev: tail,
+            next: None,
+        });
+        match tail {
+            Some(tail) => self.orders[tail].next = Some(index),
+            None => self.levels[level].head = Some(index),
         }
+        let level = &mut self.levels[level];
+        level.tail = Some(index);
+        level.len += 1;
+        level.lots += lots;
+        if aon {
+            level.aon += 1;
+            level.aon_lots += lots;
+        }
+        level.unreferenced += unreferenced as usize;
+        self.order_ids.insert(order_id, index);
+        self.owner_orders.entry(owner).or_default().insert(order_id);
         Ok(true)
     }
 
-    pub fn first_order<T, F>(&mut self, mut func:F) -> Option<T>
-    where
-        F: FnMut(Option<&mut Order>) -> Option<T>,
-    {
-        return self.first_depth(move |queue, this| {
-            let remove_order_id: Option<u64>;
-            let res: Option<T>;
-            if let Some(q) = queue {
-                if let Some(order) = q.list.first_mut() {
-                    res = func(Some(order));
-                    if order.volumn.is_zero() {
-                        remove_order_id = Some(order.id);
-                    } else {
-                        remove_order_id = None;
-                    }
-                } else {
-                    return func(None);
-                }
-                if let Some(order_id) = remove_order_id {
-                    this.remove_queue_item(q, order_id);
+    /// 按价格时间优先找到第一笔可与taker成交的挂单
+    ///
+    /// 只考虑价格不劣于tick的价位, 跳过数量超过lots的全部成交挂单
+    fn find_match(&self, tick: i64, lots: Lots) -> Option<usize> {
+        for level in self.levels_within(tick) {
+            let level = &self.levels[level];
+            if level.aon == 0 {
+                return level.head;
+            }
+            for (index, node) in self.level_nodes(level) {
+                if !node.order.all_or_none || node.lots <= lots {
+                    return Some(index);
                 }
-                return res;
-            } else {
-                return func(None);
             }
-        });
+        }
+        return None;
     }
 
-    pub fn first_depth<F, T>(&mut self, func: F) -> Option<T>
+    /// 对可与taker成交的第一笔挂单执行func, 数量为0的订单随后移除, 没有可成交挂单时返回None
+    ///
+    /// tick及lots为taker的价格及剩余数量, func须同步修改订单的volumn及Lots
+    pub fn match_order<T, F>(&mut self, tick: i64, lots: Lots, func: F) -> Option<T>
     where
-        F: FnOnce(Option<&mut OrderQueueItem>, &mut Self) -> Option<T>,
+        F: FnOnce(&mut Order, &mut Lots) -> T,
     {
-        if let Some(mut queue) = self.list.pop_front() {
-            let res = func(Some(&mut queue), self);
-            if queue.len() > 0 {
-                self.list.insert(queue);
-            }
-            return res;
-        } else {
-            return func(None, self);
+        let index = self.find_match(tick, lots)?;
+        let node = &mut self.orders[index];
+        let old_lots = node.lots;
+        let res = func(&mut node.order, &mut node.lots);
+        let (level, lots, aon) = (node.level, node.lots, node.order.all_or_none);
+        self.levels[level].lots -= old_lots - lots;
+        if aon {
+            self.levels[level].aon_lots -= old_lots - lots;
         }
+        if lots.is_zero() {
+            self.unlink(index);
+        }
+        return Some(res);
     }
 
+    /// 最优价格, 不含全部成交或不成交的挂单
     pub fn first_price(&self) -> Option<Decimal> {
-        if let Some(queue) = self.list.front() {
-            if let Some(order) = queue.list.first() {
-                return Some(order.price);
-            }
-        }
-        return None;
+        return self
+            .sorted_levels()
+            .find(|v| v.lots > v.aon_lots)
+            .map(|v| v.price);
+    }
+
+    /// 不含挂钩单及全部成交或不成交订单的最优价格, 作为挂钩单的参考价
+    pub fn first_reference_price(&self) -> Option<Decimal> {
+        return self
+            .sorted_levels()
+            .find(|v| v.len > v.unreferenced)
+            .map(|v| v.price);
+    }
+
+    /// 最优价格的tick数
+    pub fn first_tick(&self) -> Option<i64> {
+        return self.best_level().map(|v| self.levels[v].tick);
     }
 
-    #[allow(dead_code)]
+    /// 按订单号撤单, price与挂单价格不符时返回None
     pub fn remove(&mut self, price: Decimal, order_id: u64) -> Option<Order> {
-        if !self.order_ids.contains_key(&order_id) {
+        let index = *self.order_ids.get(&order_id)?;
+        if self.orders[index].order.price != price {
             return None;
         }
-        let item_queue = OrderQueueItem::new(price);
-        if let Some(mut queue) = self.list.remove_first(&item_queue) {
-            self.order_ids.remove(&order_id);
-            let order = queue.remove(order_id);
-            if !queue.list.is_empty() {
-                self.list.insert(queue);
-            }
-            return order;
-        } else {
+        return Some(self.unlink(index));
+    }
+
+    /// 在队列原位置减少订单数量, 返回原数量及修改后的订单
+    pub fn reduce_volumn(
+        &mut self,
+        price: Decimal,
+        order_id: u64,
+        volumn: Decimal,
+    ) -> Option<(Decimal, Order)> {
+        let index = *self.order_ids.get(&order_id)?;
+        let lots = self.scale.lots(volumn)?;
+        let node = &mut self.orders[index];
+        if node.order.price != price || lots > node.lots {
             return None;
         }
+        let (old_volumn, old_lots) = (node.order.volumn, node.lots);
+        node.order.volumn = volumn;
+        node.lots = lots;
+        let order = node.order.clone();
+        let level = &mut self.levels[node.level];
+        level.lots -= old_lots - lots;
+        if order.all_or_none {
+            level.aon_lots -= old_lots - lots;
+        }
+        return Some((old_volumn, order));
     }
 
-    pub fn remove_queue_item(&mut self, queue: &mut OrderQueueItem, order_id: u64) -> bool {
-        if !self.order_ids.contains_key(&order_id) {
-            return false;
+    /// 从价位链表中移除订单, 空价位一并移除
+    fn unlink(&mut self, index: usize) -> Order {
+        let node = self.orders.remove(index);
+        match node.prev {
+            Some(prev) => self.orders[prev].next = node.next,
+            None => self.levels[node.level].head = node.next,
         }
-        self.order_ids.remove(&order_id);
-        queue.remove(order_id);
-        true
+        match node.next {
+            Some(next) => self.orders[next].prev = node.prev,
+            None => self.levels[node.level].tail = node.prev,
+        }
+        let level = &mut self.levels[node.level];
+        level.len -= 1;
+        level.lots -= node.lots;
+        if node.order.all_or_none {
+            level.aon -= 1;
+            level.aon_lots -= node.lots;
+        }
+        level.unreferenced -= (node.order.all_or_none || node.order.peg.is_some()) as usize;
+        if level.len == 0 {
+            let tick = level.tick;
+            self.ticks.remove(&tick);
+            self.levels.remove(node.level);
+        }
+        self.remove_order_id(node.order.id, node.order.owner);
+        return node.order;
     }
 
-    pub fn exists_order_id(&self, order_id: u64) -> bool {
-        self.order_ids.contains_key(&order_id)
-    }
-    pub fn len(&self) -> usize {
-        let mut c = 0;
-        for i in &self.list {
-            c += i.len()
+    /// 账户挂单为空时保留索引容量, 避免反复分配
+    fn remove_order_id(&mut self, order_id: u64, owner: u64) {
+        self.order_ids.remove(&order_id);
+        if let Some(ids) = self.owner_orders.get_mut(&owner) {
+            ids.remove(&order_id);
         }
-        return c;
     }
-}
-
-pub struct OrderQueueItem {
-    pub price: Decimal,
-    pub list: Vec<Order>,
-}
 
-impl OrderQueueItem {
-    pub fn new(price: Decimal) -> OrderQueueItem {
-        return OrderQueueItem {
-            price: price,
-            list: Vec::new(),
+    /// 按账户及价格区间筛选挂单, 返回 (order_id, price)
+    pub fn select(
+        &self,
+        owner: Option<u64>,
+        min_price: Option<Decimal>,
+        max_price: Option<Decimal>,
+    ) -> Vec<(u64, Decimal)> {
+        let in_range = |price: Decimal| {
+            min_price.is_none_or(|min| price >= min) && max_price.is_none_or(|max| price <= max)
         };
+        let mut res = Vec::new();
+        if let Some(owner) = owner {
+            if let Some(ids) = self.owner_orders.get(&owner) {
+                for id in ids {
+                    let price = self.orders[self.order_ids[id]].order.price;
+                    if in_range(price) {
+                        res.push((*id, price));
+                    }
+                }
+            }
+            res.sort_unstable_by_key(|(id, _)| *id);
+        } else {
+            for level in self.sorted_levels() {
+                if in_range(level.price) {
+                    for order in self.level_orders(level) {
+                        res.push((order.id, level.price));
+                    }
+                }
+            }
+        }
+        return res;
     }
-    fn push(&mut self, order: Order) {
-        self.list.push(order);
+
+    /// 按订单号查找挂单
+    pub fn get(&self, order_id: u64) -> Option<&Order> {
+        let index = self.order_ids.get(&order_id)?;
+        return Some(&self.orders[*index].order);
     }
-    pub fn peek_mut(&mut self) -> Option<&mut Order> {
-        return self.list.first_mut();
+
+    /// 按价格优先顺序返回各价位 (价格, 挂单量), 不含全部成交或不成交的挂单
+    pub fn levels(&self) -> impl Iterator<Item = (Decimal, Decimal)> + '_ {
+        return self
+            .sorted_levels()
+            .filter(|v| v.lots > v.aon_lots)
+            .map(move |v| (v.price, self.scale.volumn(v.lots - v.aon_lots)));
     }
-    fn remove(&mut self, order_id: u64) -> Option<Order> {
-        let mut index = 0;
-        for i in &self.list {
-            if i.id == order_id {
-                return Some(self.list.remove(index));
-            } else {
-                index += 1;
+
+    /// 按价格优先顺序返回各价位 (tick数, Lots), 包括全部成交或不成交的挂单
+    pub fn level_lots(&self) -> impl Iterator<Item = (i64, Lots)> + '_ {
+        return self.sorted_levels().map(|v| (v.tick, v.lots));
+    }
+
+    /// 价格不劣于tick, 数量为max的taker可以成交的数量
+    ///
+    /// 与撮合时一样跳过无法全部成交的挂单
+    pub fn available(&self, tick: i64, max: Lots) -> Lots {
+        let mut lots = Lots::zero();
+        for level in self.levels_within(tick) {
+            if lots >= max {
+                break;
+            }
+            let level = &self.levels[level];
+            if level.aon == 0 {
+                lots += min(level.lots, max - lots);
+                continue;
+            }
+            for (_, node) in self.level_nodes(level) {
+                let rest = max - lots;
+                if rest.is_zero() {
+                    break;
+                }
+                if !node.order.all_or_none || node.lots <= rest {
+                    lots += min(node.lots, rest);
+                }
             }
         }
-        return None;
+        return lots;
     }
-    #[allow(dead_code)]
-    pub fn len(&self) -> usize {
-        return self.list.len();
+
+    /// 前limit档价格及挂单量, 不含全部成交或不成交的挂单
+    pub fn depth(&self, limit: usize) -> Vec<(Decimal, Decimal)> {
+        return self.levels().take(limit).collect();
     }
-    #[allow(dead_code)]
-    pub fn volumn(&self) -> Decimal {
-        let mut volumn = Decimal::zero();
-        for i in &self.list {
-            volumn += i.volumn
+
+    /// 修改所有挂单的symbol
+    pub fn set_symbol(&mut self, symbol: &Symbol) {
+        for (_, node) in self.orders.iter_mut() {
+            node.order.symbol = symbol.clone();
         }
-        return volumn;
+    }
+
+    pub fn exists_order_id(&self, order_id: u64) -> bool {
+        self.order_ids.contains_key(&order_id)
+    }
+    pub fn len(&self) -> usize {
+        return self.order_ids.len();
     }
 }
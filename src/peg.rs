@@ -0,0 +1,77 @@
+use rust_decimal::{prelude::Zero, Decimal};
+use serde::{Deserialize, Serialize};
+
+use crate::OrderSide;
+
+/// 挂钩参考价
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PegKind {
+    /// 同方向最优价
+    Primary,
+    /// 对手方最优价
+    Market,
+    /// 买一卖一中间价
+    Midpoint,
+}
+
+/// 挂钩单参数, 价格随盘口浮动
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Peg {
+    pub kind: PegKind,
+    /// 在参考价上加减的偏移, 可为负数
+    pub offset: Decimal,
+    /// 价格上限(买)或下限(卖)
+    pub limit: Option<Decimal>,
+}
+
+impl Peg {
+    pub fn new(kind: PegKind) -> Peg {
+        Peg {
+            kind,
+            offset: Decimal::zero(),
+            limit: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Decimal) -> Peg {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: Decimal) -> Peg {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// 按参考价计算挂单价格, bid ask为不含挂钩单的买一卖一价
+    ///
+    /// 买单向下, 卖单向上取整到tick_size, 缺少参考价或价格不为正时返回None
+    pub fn price(
+        &self,
+        side: OrderSide,
+        bid: Option<Decimal>,
+        ask: Option<Decimal>,
+        tick_size: Decimal,
+    ) -> Option<Decimal> {
+        let reference = match (self.kind, side) {
+            (PegKind::Primary, OrderSide::Buy) | (PegKind::Market, OrderSide::Sell) => bid?,
+            (PegKind::Primary, OrderSide::Sell) | (PegKind::Market, OrderSide::Buy) => ask?,
+            (PegKind::Midpoint, _) => (bid? + ask?) / Decimal::from(2),
+        };
+        let ticks = (reference + self.offset) / tick_size;
+        let price = match side {
+            OrderSide::Buy => {
+                let price = ticks.floor() * tick_size;
+                self.limit.map_or(price, |v| price.min(v))
+            }
+            OrderSide::Sell => {
+                let price = ticks.ceil() * tick_size;
+                self.limit.map_or(price, |v| price.max(v))
+            }
+        };
+        if price <= Decimal::zero() {
+            return None;
+        }
+        return Some(price.normalize());
+    }
+}
@@ -1,22 +1,107 @@
 use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{FeeAsset, Order, OrderSide};
+
+/// 成交记录
+#[derive(Clone, Debug, Serialize)]
+pub struct Trade {
+    /// 成交序号, 每个标的从1开始递增
+    pub trade_id: u64,
+    pub maker_id: u64,
+    pub taker_id: u64,
+    pub maker_owner: u64,
+    pub taker_owner: u64,
+    /// 成交双方下单时的客户订单号
+    pub maker_client_order_id: Option<String>,
+    pub taker_client_order_id: Option<String>,
+    pub taker_side: OrderSide,
+    pub price: Decimal,
+    pub volumn: Decimal,
+    /// maker手续费, 负数为返佣
+    pub maker_fee: Decimal,
+    pub taker_fee: Decimal,
+    pub fee_asset: FeeAsset,
+    /// 成交时间, 毫秒, 由订单簿按引擎时钟写入
+    pub timestamp: i64,
+}
 
 pub trait ResultQueue{
+    /// 订单通过检查, 开始撮合
+    #[allow(unused_variables)]
+    fn order_accepted(&self,order:&Order) {
+    }
+
+    /// 订单被拒绝
+    #[allow(unused_variables)]
+    fn order_rejected(&self,order:&Order,reason:&'static str) {
+    }
+
     /// 成交成功
-    fn trade_success(&self,maker_id:u64,taker_id:u64,volumn:Decimal,price:Decimal);
+    fn trade_success(&self,trade:&Trade);
+
+    /// 订单取消, order.volumn为撤销数量 (包括主动撤单, 批量撤单及未成交部分自动撤销)
+    fn cancel_order(&self,order:&Order);
 
-    /// 订单被动取消 （主动撤销订单不会调用)
-    fn cancel_order(&self,order_id:u64,volumn:Decimal);
+    /// 订单修改成功, order为修改后的订单
+    #[allow(unused_variables)]
+    fn order_amended(&self,order:&Order) {
+    }
+
+    /// 盘口价位挂单量变化, volumn为变化量
+    #[allow(unused_variables)]
+    fn depth_changed(&self,side:OrderSide,price:Decimal,volumn:Decimal) {
+    }
+}
+
+/// 将结果依次分发给多个队列
+impl ResultQueue for Vec<Box<dyn ResultQueue + Send + Sync>> {
+    fn order_accepted(&self,order:&Order) {
+        for queue in self {
+            queue.order_accepted(order);
+        }
+    }
+
+    fn order_rejected(&self,order:&Order,reason:&'static str) {
+        for queue in self {
+            queue.order_rejected(order, reason);
+        }
+    }
+
+    fn trade_success(&self,trade:&Trade) {
+        for queue in self {
+            queue.trade_success(trade);
+        }
+    }
+
+    fn cancel_order(&self,order:&Order) {
+        for queue in self {
+            queue.cancel_order(order);
+        }
+    }
+
+    fn order_amended(&self,order:&Order) {
+        for queue in self {
+            queue.order_amended(order);
+        }
+    }
+
+    fn depth_changed(&self,side:OrderSide,price:Decimal,volumn:Decimal) {
+        for queue in self {
+            queue.depth_changed(side, price, volumn);
+        }
+    }
 }
 pub struct NoneQueue{
 }
 
 impl ResultQueue for NoneQueue {
     #[allow(unused_variables)]
-    fn trade_success(&self,maker_id:u64,taker_id:u64,volumn:Decimal,price:Decimal) {
-       
+    fn trade_success(&self,trade:&Trade) {
+
     }
 
     #[allow(unused_variables)]
-    fn cancel_order(&self,order_id:u64,volumn:Decimal) {
+    fn cancel_order(&self,order:&Order) {
     }
 }
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use rust_decimal::{prelude::Zero, Decimal};
+use serde::{Deserialize, Serialize};
+
+use crate::{Lots, Order, OrderKind, OrderSide, Scale};
+
+/// 账户风控限制, None为不限制
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RiskLimits {
+    /// 单笔最大数量
+    pub max_volumn: Option<Decimal>,
+    /// 单笔最大金额
+    pub max_notional: Option<Decimal>,
+    /// 单个标的最大挂单数
+    pub max_open_orders: Option<usize>,
+    /// 单个标的最大净持仓 (含挂单)
+    pub max_position: Option<Decimal>,
+    /// 每秒最大下单数
+    pub max_order_rate: Option<u32>,
+}
+
+impl RiskLimits {
+    /// 检查订单是否超出限制, price为参考成交价
+    pub fn check(
+        &self,
+        order: &Order,
+        price: Decimal,
+        exposure: &Exposure,
+    ) -> Result<(), &'static str> {
+        if let Some(max_volumn) = self.max_volumn {
+            if order.volumn > max_volumn {
+                return Err("order volumn exceeds limit");
+            }
+        }
+        if let Some(max_notional) = self.max_notional {
+            if order.volumn * price > max_notional {
+                return Err("order notional exceeds limit");
+            }
+        }
+        if let Some(max_open_orders) = self.max_open_orders {
+            if let OrderKind::Limit = order.kind {
+                if exposure.open_orders >= max_open_orders {
+                    return Err("too many open orders");
+                }
+            }
+        }
+        if let Some(max_position) = self.max_position {
+            // 按所有挂单全部成交计算最坏持仓
+            let position = match order.side {
+                OrderSide::Buy => exposure.position + exposure.open_buy + order.volumn,
+                OrderSide::Sell => -(exposure.position - exposure.open_sell - order.volumn),
+            };
+            if position > max_position {
+                return Err("position limit exceeded");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 账户在单个标的上的挂单及持仓
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Exposure {
+    pub open_orders: usize,
+    pub open_buy: Decimal,
+    pub open_sell: Decimal,
+    /// 净持仓, 正数为多头
+    pub position: Decimal,
+}
+
+/// 按Lots记录的账户敞口, 成交时不做Decimal运算
+#[derive(Clone, Default)]
+struct LotExposure {
+    open_orders: usize,
+    open_buy: Lots,
+    open_sell: Lots,
+    position: Lots,
+}
+
+/// 由撮合事件增量维护的账户敞口, 数量按订单簿的lot_size换算为Lots
+pub struct RiskState {
+    scale: Scale,
+    accounts: HashMap<u64, LotExposure>,
+}
+
+impl RiskState {
+    pub fn new(scale: Scale) -> RiskState {
+        RiskState {
+            scale,
+            accounts: HashMap::new(),
+        }
+    }
+
+    /// 查询时换算为数量
+    pub fn exposure(&self, owner: u64) -> Option<Exposure> {
+        let exposure = self.accounts.get(&owner)?;
+        return Some(Exposure {
+            open_orders: exposure.open_orders,
+            open_buy: self.scale.volumn(exposure.open_buy),
+            open_sell: self.scale.volumn(exposure.open_sell),
+            position: self.scale.volumn(exposure.position),
+        });
+    }
+
+    /// 订单数量已按lot_size检查, 换算不会失败
+    fn lots(&self, volumn: Decimal) -> Lots {
+        return self.scale.lots(volumn).unwrap_or_else(Lots::zero);
+    }
+
+    /// 订单进入挂单队列
+    pub(crate) fn on_rest(&mut self, order: &Order) {
+        let lots = self.lots(order.volumn);
+        let exposure = self.accounts.entry(order.owner).or_default();
+        exposure.open_orders += 1;
+        match order.side {
+            OrderSide::Buy => exposure.open_buy += lots,
+            OrderSide::Sell => exposure.open_sell += lots,
+        }
+    }
+
+    /// 挂单成交, maker为成交后的订单, lots为成交数量
+    pub(crate) fn on_fill(&mut self, maker: &Order, taker_owner: u64, lots: Lots) {
+        let exposure = self.accounts.entry(maker.owner).or_default();
+        match maker.side {
+            OrderSide::Buy => {
+                exposure.open_buy -= lots;
+                exposure.position += lots;
+            }
+            OrderSide::Sell => {
+                exposure.open_sell -= lots;
+                exposure.position -= lots;
+            }
+        }
+        if maker.volumn.is_zero() {
+            exposure.open_orders -= 1;
+        }
+        let exposure = self.accounts.entry(taker_owner).or_default();
+        match maker.side {
+            OrderSide::Buy => exposure.position -= lots,
+            OrderSide::Sell => exposure.position += lots,
+        }
+    }
+
+    /// 挂单数量减少
+    pub(crate) fn on_reduce(&mut self, order: &Order, volumn: Decimal) {
+        let lots = self.lots(volumn);
+        if let Some(exposure) = self.accounts.get_mut(&order.owner) {
+            match order.side {
+                OrderSide::Buy => exposure.open_buy -= lots,
+                OrderSide::Sell => exposure.open_sell -= lots,
+            }
+        }
+    }
+
+    /// 挂单被撤销
+    pub(crate) fn on_remove(&mut self, order: &Order) {
+        let lots = self.lots(order.volumn);
+        if let Some(exposure) = self.accounts.get_mut(&order.owner) {
+            exposure.open_orders -= 1;
+            match order.side {
+                OrderSide::Buy => exposure.open_buy -= lots,
+                OrderSide::Sell => exposure.open_sell -= lots,
+            }
+        }
+    }
+}
+
+/// 账户风控配置及下单频率统计
+#[derive(Default)]
+pub struct RiskManager {
+    pub default_limits: RiskLimits,
+    limits: HashMap<u64, RiskLimits>,
+    /// 账户 -> (统计开始时间, 下单数)
+    rates: HashMap<u64, (i64, u32)>,
+}
+
+impl RiskManager {
+    pub fn set_limits(&mut self, owner: u64, limits: RiskLimits) {
+        self.limits.insert(owner, limits);
+    }
+
+    pub fn limits(&self, owner: u64) -> &RiskLimits {
+        self.limits.get(&owner).unwrap_or(&self.default_limits)
+    }
+
+    /// 统计下单频率, 超出每秒限制时拒绝, now为毫秒时间
+    pub fn check_rate(&mut self, owner: u64, now: i64) -> Result<(), &'static str> {
+        let max_order_rate = match self.limits(owner).max_order_rate {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let (start, count) = self.rates.entry(owner).or_insert((now, 0));
+        if now - *start >= 1000 {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= max_order_rate {
+            return Err("order rate exceeded");
+        }
+        *count += 1;
+        Ok(())
+    }
+}
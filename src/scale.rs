@@ -0,0 +1,74 @@
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+/// 订单队列中的挂单量
+///
+/// 默认为Decimal, 启用fixed-point后为按lot_size换算的整数, 用于价位挂单量 撮合对象查找
+/// 可成交数量检查, 以及撮合过程中taker的剩余数量 成交数量及风控敞口.
+/// 只在生成成交事件 手续费 订单状态及挂单的订单数量时换算为Decimal
+#[cfg(not(feature = "fixed-point"))]
+pub type Lots = Decimal;
+#[cfg(feature = "fixed-point")]
+pub type Lots = i64;
+
+/// 价格数量与整数tick/lot的换算, 在订单进出订单簿及生成成交事件时使用
+#[derive(Clone, Copy, Debug)]
+pub struct Scale {
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+}
+
+impl Scale {
+    pub fn new(tick_size: Decimal, lot_size: Decimal) -> Scale {
+        return Scale {
+            tick_size,
+            lot_size,
+        };
+    }
+
+    /// 价格换算为tick数, 不是tick_size整数倍时返回None
+    pub fn ticks(&self, price: Decimal) -> Option<i64> {
+        return exact_div(price, self.tick_size);
+    }
+
+    /// 数量换算为Lots, 整数模式下不是lot_size整数倍时返回None
+    #[cfg(not(feature = "fixed-point"))]
+    pub fn lots(&self, volumn: Decimal) -> Option<Lots> {
+        return Some(volumn);
+    }
+
+    #[cfg(feature = "fixed-point")]
+    pub fn lots(&self, volumn: Decimal) -> Option<Lots> {
+        return exact_div(volumn, self.lot_size);
+    }
+
+    /// Lots换算回数量
+    #[cfg(not(feature = "fixed-point"))]
+    pub fn volumn(&self, lots: Lots) -> Decimal {
+        return lots;
+    }
+
+    #[cfg(feature = "fixed-point")]
+    pub fn volumn(&self, lots: Lots) -> Decimal {
+        let mantissa = lots as i128 * self.lot_size.mantissa();
+        return Decimal::from_i128_with_scale(mantissa, self.lot_size.scale()).normalize();
+    }
+}
+
+/// value / unit, 用尾数做整数运算避免Decimal除法
+fn exact_div(value: Decimal, unit: Decimal) -> Option<i64> {
+    if unit.is_zero() {
+        return None;
+    }
+    // value = m / 10^s, unit = u / 10^t, value / unit = m * 10^t / (u * 10^s)
+    let (mut num, mut den) = (value.mantissa(), unit.mantissa());
+    let (s, t) = (value.scale(), unit.scale());
+    if t > s {
+        num = num.checked_mul(10i128.checked_pow(t - s)?)?;
+    } else {
+        den = den.checked_mul(10i128.checked_pow(s - t)?)?;
+    }
+    if num % den != 0 {
+        return None;
+    }
+    return (num / den).to_i64();
+}
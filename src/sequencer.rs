@@ -0,0 +1,186 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use rust_decimal::Decimal;
+
+use crate::{CancelFilter, LocalEngine, Order, OrderSide};
+
+pub(crate) type Command = Box<dyn FnOnce(&mut LocalEngine) + Send>;
+
+/// 命令执行结果
+pub struct Completion<T> {
+    receiver: Receiver<Result<T, &'static str>>,
+}
+
+impl<T> Completion<T> {
+    /// 阻塞等待结果, 撮合线程已退出或命令panic时返回错误
+    pub fn wait(self) -> Result<T, &'static str> {
+        return self.receiver.recv().map_err(|_| "sequencer stopped")?;
+    }
+
+    /// 结果未就绪时返回None
+    pub fn try_wait(&self) -> Option<Result<T, &'static str>> {
+        return self.receiver.try_recv().ok();
+    }
+}
+
+/// 在撮合线程执行命令, panic时返回错误
+pub(crate) fn run<F, T>(engine: &mut LocalEngine, func: F) -> Result<T, &'static str>
+where
+    F: FnOnce(&mut LocalEngine) -> T,
+{
+    return panic::catch_unwind(AssertUnwindSafe(|| func(engine))).map_err(|_| "command panicked");
+}
+
+/// 单写者撮合模式
+///
+/// 命令经有界无锁环形队列交给独占LocalEngine的撮合线程顺序执行, 执行顺序即入队顺序,
+/// 撮合过程不加锁. 可每个标的一个Sequencer, 也可多个标的共用一个.
+///
+/// 命令panic时该命令返回"command panicked", 撮合线程继续执行后续命令,
+/// 但panic发生时正在修改的订单簿可能只更新了一部分
+pub struct Sequencer {
+    sender: Option<Sender<Command>>,
+    handle: Option<JoinHandle<LocalEngine>>,
+}
+
+impl Sequencer {
+    /// # Examples
+    ///
+    /// ```
+    /// use match_trade::*;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    /// let sequencer = Sequencer::new(LocalEngine::new(), 1024);
+    /// sequencer.execute(|engine| engine.add_book("DOGE/BTC", None).map(|_| true)).wait().unwrap().unwrap();
+    /// let order = Order::new(1,"DOGE/BTC",Decimal::from_str("1").unwrap(),Decimal::from_str("1").unwrap(),OrderKind::Limit,OrderSide::Buy,1000000000);
+    /// sequencer.add_order(order.clone()).wait().unwrap().unwrap();
+    /// sequencer.cancel_order("DOGE/BTC",order.id,order.price,order.side).wait().unwrap().unwrap();
+    /// ```
+    pub fn new(mut engine: LocalEngine, capacity: usize) -> Sequencer {
+        let (sender, receiver) = bounded::<Command>(capacity);
+        let handle = thread::Builder::new()
+            .name("match-sequencer".to_string())
+            .spawn(move || {
+                for command in receiver.iter() {
+                    // 命令自行处理panic, 这里兜底保证撮合线程不退出
+                    let _ = run(&mut engine, command);
+                }
+                return engine;
+            })
+            .unwrap();
+        return Sequencer {
+            sender: Some(sender),
+            handle: Some(handle),
+        };
+    }
+
+    /// 在撮合线程执行, 队列满时阻塞
+    pub fn execute<F, T>(&self, func: F) -> Completion<T>
+    where
+        F: FnOnce(&mut LocalEngine) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (command, completion) = Sequencer::command(func);
+        // 撮合线程已退出时command被丢弃, wait返回错误
+        let _ = self.sender.as_ref().unwrap().send(command);
+        return completion;
+    }
+
+    /// 在撮合线程执行, 队列满时返回错误
+    pub fn try_execute<F, T>(&self, func: F) -> Result<Completion<T>, &'static str>
+    where
+        F: FnOnce(&mut LocalEngine) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (command, completion) = Sequencer::command(func);
+        self.try_send(command)?;
+        return Ok(completion);
+    }
+
+    /// 非阻塞投递命令
+    pub(crate) fn try_send(&self, command: Command) -> Result<(), &'static str> {
+        return match self.sender.as_ref().unwrap().try_send(command) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("sequencer full"),
+            Err(TrySendError::Disconnected(_)) => Err("sequencer stopped"),
+        };
+    }
+
+    fn command<F, T>(func: F) -> (Command, Completion<T>)
+    where
+        F: FnOnce(&mut LocalEngine) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = bounded(1);
+        let command: Command = Box::new(move |engine: &mut LocalEngine| {
+            let _ = sender.send(run(engine, func));
+        });
+        return (command, Completion { receiver });
+    }
+
+    /// 新建order, 完成时返回订单号
+    pub fn add_order(&self, order: Order) -> Completion<Result<u64, &'static str>> {
+        return self.execute(move |engine| engine.add_order(order));
+    }
+
+    /// 撤销order
+    pub fn cancel_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+    ) -> Completion<Option<Order>> {
+        let symbol = symbol.to_string();
+        return self.execute(move |engine| engine.cancel_order(&symbol, order_id, price, side));
+    }
+
+    /// 修改order
+    pub fn amend_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Completion<Result<bool, &'static str>> {
+        let symbol = symbol.to_string();
+        return self.execute(move |engine| {
+            engine.amend_order(&symbol, order_id, price, side, new_price, new_volumn)
+        });
+    }
+
+    /// 批量撤单
+    pub fn mass_cancel(&self, filter: CancelFilter) -> Completion<Vec<Order>> {
+        return self.execute(move |engine| engine.mass_cancel(&filter));
+    }
+
+    /// 队列中待执行的命令数
+    pub fn pending(&self) -> usize {
+        return self.sender.as_ref().unwrap().len();
+    }
+
+    /// 执行完已入队的命令后停止撮合线程, 返回LocalEngine
+    pub fn stop(mut self) -> Result<LocalEngine, &'static str> {
+        return self.join();
+    }
+
+    fn join(&mut self) -> Result<LocalEngine, &'static str> {
+        self.sender.take();
+        return match self.handle.take() {
+            Some(handle) => handle.join().map_err(|_| "sequencer stopped"),
+            None => Err("sequencer stopped"),
+        };
+    }
+}
+
+impl Drop for Sequencer {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    CancelFilter, Clock, Completion, Depth, Exposure, FeeSchedule, Instrument, LocalEngine, Order,
+    OrderIds, OrderSide, ResultQueue, RiskLimits, Sequencer, Symbol, SystemClock, TradingStatus,
+};
+
+/// 每个分片在哈希环上的虚拟节点数
+const VIRTUAL_NODES: usize = 64;
+
+/// 分片负载, 用于发现热点标的后迁移
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ShardMetrics {
+    pub shard: usize,
+    /// 已执行命令数
+    pub commands: u64,
+    /// 队列中待执行命令数
+    pub pending: usize,
+    /// 各标的已执行命令数
+    pub symbols: HashMap<String, u64>,
+}
+
+struct Shard {
+    sequencer: Sequencer,
+    /// 已执行命令数, 各标的命令数由撮合线程记录在LocalEngine中
+    commands: Arc<AtomicU64>,
+}
+
+/// 多线程分片Engine
+///
+/// 标的按一致性哈希分配到各分片, 每个分片的订单簿由独立的撮合线程独占, 撮合不加锁.
+/// 账户手续费等级及风控限制下发到所有分片, 下单频率按分片分别统计
+pub struct ShardedEngine {
+    shards: Vec<Shard>,
+    /// 哈希环, 按哈希值排序
+    ring: Vec<(u64, usize)>,
+    /// 手动迁移过的标的
+    pins: RwLock<HashMap<String, usize>>,
+}
+
+impl ShardedEngine {
+    /// # Examples
+    ///
+    /// ```
+    /// use match_trade::*;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    /// let engine = ShardedEngine::new(4, 1024);
+    /// engine.add_book("DOGE/BTC",None).unwrap();
+    /// let order = Order::new(1,"DOGE/BTC",Decimal::from_str("1").unwrap(),Decimal::from_str("1").unwrap(),OrderKind::Limit,OrderSide::Buy,1000000000);
+    /// engine.add_order(order.clone()).unwrap();
+    /// engine.cancel_order("DOGE/BTC",order.id,order.price,order.side).unwrap();
+    /// ```
+    pub fn new(shards: usize, capacity: usize) -> ShardedEngine {
+        return ShardedEngine::with_clock(shards, capacity, Arc::new(SystemClock));
+    }
+
+    /// 所有分片使用指定的时钟
+    pub fn with_clock(shards: usize, capacity: usize, clock: Arc<dyn Clock>) -> ShardedEngine {
+        assert!(shards > 0, "shards must be greater than 0");
+        let mut ring = Vec::with_capacity(shards * VIRTUAL_NODES);
+        for shard in 0..shards {
+            for node in 0..VIRTUAL_NODES {
+                let key = [(shard as u64).to_le_bytes(), (node as u64).to_le_bytes()].concat();
+                ring.push((hash(&key), shard));
+            }
+        }
+        ring.sort_unstable();
+        // 所有分片共用订单号分配器, 分配的订单号在各分片间不重复
+        let order_ids = Arc::new(OrderIds::default());
+        return ShardedEngine {
+            shards: (0..shards)
+                .map(|_| {
+                    let mut engine = LocalEngine::with_clock(clock.clone());
+                    engine.order_ids = order_ids.clone();
+                    Shard {
+                        sequencer: Sequencer::new(engine, capacity),
+                        commands: Arc::new(AtomicU64::new(0)),
+                    }
+                })
+                .collect(),
+            ring,
+            pins: RwLock::new(HashMap::new()),
+        };
+    }
+
+    /// 分片数
+    pub fn shards(&self) -> usize {
+        return self.shards.len();
+    }
+
+    /// 标的所在分片
+    pub fn shard_of(&self, symbol: &str) -> usize {
+        return self.locate(&self.pins.read().unwrap(), symbol);
+    }
+
+    fn locate(&self, pins: &HashMap<String, usize>, symbol: &str) -> usize {
+        if let Some(shard) = pins.get(symbol) {
+            return *shard;
+        }
+        let key = hash(symbol.as_bytes());
+        let index = self.ring.partition_point(|(v, _)| *v < key);
+        return self.ring[index % self.ring.len()].1;
+    }
+
+    /// 在标的所在分片执行, 已添加的标的名称已驻留, 投递命令不分配字符串.
+    /// 按名称查找标的时只查找已驻留的名称, 未驻留的名称不存在对应的标的
+    ///
+    /// 执行后标的存在时才计入该标的的命令数, 不存在的标的不会留下记录
+    fn execute<F, T>(&self, symbol: Symbol, func: F) -> Completion<T>
+    where
+        F: FnOnce(&mut LocalEngine, &str) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        // 入队完成前持有读锁, 迁移标的时不会投递到旧分片
+        let pins = self.pins.read().unwrap();
+        let shard = &self.shards[self.locate(&pins, &symbol)];
+        let commands = shard.commands.clone();
+        return shard.sequencer.execute(move |engine| {
+            commands.fetch_add(1, Ordering::Relaxed);
+            let result = func(engine, &symbol);
+            engine.record(&symbol);
+            result
+        });
+    }
+
+    /// 在所有分片执行
+    fn broadcast<F, T>(&self, func: F) -> Vec<T>
+    where
+        F: FnOnce(&mut LocalEngine) -> T + Clone + Send + 'static,
+        T: Send + 'static,
+    {
+        let completions: Vec<Completion<T>> = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let commands = shard.commands.clone();
+                let func = func.clone();
+                shard.sequencer.execute(move |engine| {
+                    commands.fetch_add(1, Ordering::Relaxed);
+                    func(engine)
+                })
+            })
+            .collect();
+        return completions.into_iter().filter_map(|v| v.wait().ok()).collect();
+    }
+
+    /// 添加标的
+    pub fn add_book(
+        &self,
+        symbol: &str,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Result<bool, &'static str> {
+        return self.add_instrument(Instrument::new(symbol), result_queue);
+    }
+
+    /// 按标的配置添加标的
+    pub fn add_instrument(
+        &self,
+        instrument: Instrument,
+        result_queue: Option<Box<dyn ResultQueue + Send + Sync>>,
+    ) -> Result<bool, &'static str> {
+        let symbol = Symbol::new(&instrument.symbol);
+        return self
+            .execute(symbol, move |engine, _| engine.add_instrument(instrument, result_queue))
+            .wait()
+            .and_then(|v| v);
+    }
+
+    /// 下架标的, 撤销所有挂单并返回
+    pub fn remove_book(&self, symbol: &str) -> Result<Vec<Order>, &'static str> {
+        let symbol = Symbol::lookup(symbol).ok_or("not found symbol")?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.remove_book(symbol))
+            .wait()
+            .and_then(|v| v);
+    }
+
+    /// 将标的连同挂单迁移到指定分片
+    pub fn move_book(&self, symbol: &str, shard: usize) -> Result<bool, &'static str> {
+        if shard >= self.shards.len() {
+            return Err("invalid shard");
+        }
+        // 迁移期间阻止新命令入队
+        let mut pins = self.pins.write().unwrap();
+        let from = self.locate(&pins, symbol);
+        let name = Symbol::lookup(symbol).ok_or("not found symbol")?;
+        if from == shard {
+            return match self.shards[from].sequencer.execute(move |engine| engine.book(&name).is_some()).wait()? {
+                true => Ok(true),
+                false => Err("not found symbol"),
+            };
+        }
+        // 订单簿连同命令数一起迁移
+        let moved = match self.shards[from]
+            .sequencer
+            .execute(move |engine| engine.take_moved(&name))
+            .wait()?
+        {
+            Some(moved) => moved,
+            None => return Err("not found symbol"),
+        };
+        let inserted = self.shards[shard]
+            .sequencer
+            .execute(move |engine| engine.insert_moved(moved))
+            .wait()?;
+        if let Err(moved) = inserted {
+            self.shards[from]
+                .sequencer
+                .execute(move |engine| engine.insert_moved(moved).map_err(|_| "duplicate symbol"))
+                .wait()??;
+            return Err("duplicate symbol");
+        }
+        pins.insert(symbol.to_string(), shard);
+        return Ok(true);
+    }
+
+    /// 各分片负载, 各标的命令数由撮合线程读取, 不计入命令数
+    pub fn metrics(&self) -> Vec<ShardMetrics> {
+        let metrics: Vec<(ShardMetrics, Completion<HashMap<String, u64>>)> = self
+            .shards
+            .iter()
+            .enumerate()
+            .map(|(i, shard)| {
+                let metrics = ShardMetrics {
+                    shard: i,
+                    commands: shard.commands.load(Ordering::Relaxed),
+                    pending: shard.sequencer.pending(),
+                    symbols: HashMap::new(),
+                };
+                let symbols = shard.sequencer.execute(|engine| {
+                    return engine
+                        .commands
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), *v))
+                        .collect();
+                });
+                (metrics, symbols)
+            })
+            .collect();
+        return metrics
+            .into_iter()
+            .map(|(mut metrics, symbols)| {
+                metrics.symbols = symbols.wait().unwrap_or_default();
+                metrics
+            })
+            .collect();
+    }
+
+    /// 查看所有标的配置
+    pub fn symbols(&self) -> Vec<Instrument> {
+        let mut symbols: Vec<Instrument> = self
+            .broadcast(|engine| engine.symbols())
+            .into_iter()
+            .flatten()
+            .collect();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        return symbols;
+    }
+
+    /// 设置交易状态
+    pub fn set_trading_status(&self, symbol: &str, status: TradingStatus) -> Result<bool, &'static str> {
+        let symbol = Symbol::lookup(symbol).ok_or("not found symbol")?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.set_trading_status(symbol, status))
+            .wait()
+            .and_then(|v| v);
+    }
+
+    /// 设置标的手续费
+    pub fn set_fee_schedule(&self, symbol: &str, fee_schedule: FeeSchedule) -> Result<bool, &'static str> {
+        let symbol = Symbol::lookup(symbol).ok_or("not found symbol")?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.set_fee_schedule(symbol, fee_schedule))
+            .wait()
+            .and_then(|v| v);
+    }
+
+    /// 设置账户手续费等级, 对之后提交的订单生效
+    pub fn set_fee_tier(&self, owner: u64, tier: u32) {
+        self.broadcast(move |engine| engine.set_fee_tier(owner, tier));
+    }
+
+    /// 设置默认风控限制
+    pub fn set_default_risk_limits(&self, limits: RiskLimits) {
+        self.broadcast(move |engine| engine.set_default_risk_limits(limits));
+    }
+
+    /// 设置账户风控限制
+    pub fn set_risk_limits(&self, owner: u64, limits: RiskLimits) {
+        self.broadcast(move |engine| engine.set_risk_limits(owner, limits));
+    }
+
+    /// 查看盘口深度
+    pub fn depth(&self, symbol: &str, limit: usize) -> Option<Depth> {
+        let symbol = Symbol::lookup(symbol)?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.depth(symbol, limit))
+            .wait()
+            .ok()
+            .flatten();
+    }
+
+    /// 查看挂单
+    pub fn get_order(&self, symbol: &str, order_id: u64) -> Option<Order> {
+        let symbol = Symbol::lookup(symbol)?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.get_order(symbol, order_id))
+            .wait()
+            .ok()
+            .flatten();
+    }
+
+    /// 查看账户挂单, symbol为None时查询所有标的
+    pub fn open_orders(&self, symbol: Option<&str>, owner: u64) -> Vec<Order> {
+        if let Some(symbol) = symbol {
+            return match Symbol::lookup(symbol) {
+                Some(symbol) => self
+                    .execute(symbol, move |engine, symbol| engine.open_orders(Some(symbol), owner))
+                    .wait()
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+        }
+        return self
+            .broadcast(move |engine| engine.open_orders(None, owner))
+            .into_iter()
+            .flatten()
+            .collect();
+    }
+
+    /// 查看账户在标的上的挂单及持仓
+    pub fn exposure(&self, symbol: &str, owner: u64) -> Option<Exposure> {
+        let symbol = Symbol::lookup(symbol)?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.exposure(symbol, owner))
+            .wait()
+            .ok()
+            .flatten();
+    }
+
+    /// 新建order, 返回订单号
+    pub fn add_order(&self, order: Order) -> Result<u64, &'static str> {
+        return self
+            .execute(order.symbol.clone(), move |engine, _| engine.add_order(order))
+            .wait()
+            .and_then(|v| v);
+    }
+
+    /// 撤销order
+    pub fn cancel_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+    ) -> Option<Order> {
+        let symbol = Symbol::lookup(symbol)?;
+        return self
+            .execute(symbol, move |engine, symbol| engine.cancel_order(symbol, order_id, price, side))
+            .wait()
+            .ok()
+            .flatten();
+    }
+
+    /// 修改order
+    pub fn amend_order(
+        &self,
+        symbol: &str,
+        order_id: u64,
+        price: Decimal,
+        side: OrderSide,
+        new_price: Decimal,
+        new_volumn: Decimal,
+    ) -> Result<bool, &'static str> {
+        let symbol = Symbol::lookup(symbol).ok_or("not found symbol")?;
+        return self
+            .execute(symbol, move |engine, symbol| {
+                engine.amend_order(symbol, order_id, price, side, new_price, new_volumn)
+            })
+            .wait()
+            .and_then(|v| v);
+    }
+
+    /// 批量撤单, 返回被撤销的订单
+    pub fn mass_cancel(&self, filter: &CancelFilter) -> Vec<Order> {
+        let filter = filter.clone();
+        if let Some(symbol) = &filter.symbol {
+            return match Symbol::lookup(symbol) {
+                Some(symbol) => self
+                    .execute(symbol, move |engine, _| engine.mass_cancel(&filter))
+                    .wait()
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+        }
+        return self
+            .broadcast(move |engine| engine.mass_cancel(&filter))
+            .into_iter()
+            .flatten()
+            .collect();
+    }
+
+    /// 查看买单，卖单总订单数
+    pub fn len(&self) -> (usize, usize) {
+        return self
+            .broadcast(|engine| engine.len())
+            .into_iter()
+            .fold((0, 0), |(buy, sell), (b, s)| (buy + b, sell + s));
+    }
+}
+
+/// FNV-1a, 不随Rust版本及进程变化, 标的在每次启动时分配到相同的分片
+///
+/// 最后按MurmurHash3的fmix64打散, 相近的输入在哈希环上分布更均匀
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    return hash;
+}
@@ -0,0 +1,99 @@
+use rust_decimal::{prelude::Zero, Decimal};
+use serde::{Deserialize, Serialize};
+
+use crate::OrderSide;
+
+/// 止损触发方式
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum StopKind {
+    /// 固定触发价
+    Fixed,
+    /// 与最高(卖)或最低(买)成交价保持固定价差
+    TrailingAmount(Decimal),
+    /// 与最高(卖)或最低(买)成交价保持固定百分比, 5表示5%
+    TrailingPercent(Decimal),
+}
+
+/// 止损单参数, 最新成交价达到触发价后按订单类型下单
+///
+/// 买单在成交价不低于触发价时触发, 卖单在成交价不高于触发价时触发
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Stop {
+    pub kind: StopKind,
+    /// 当前触发价, 追踪止损在出现成交价之前为None
+    pub trigger_price: Option<Decimal>,
+    /// 追踪止损记录的最高成交价(卖)或最低成交价(买)
+    pub water_mark: Option<Decimal>,
+}
+
+impl Stop {
+    pub fn new(trigger_price: Decimal) -> Stop {
+        Stop {
+            kind: StopKind::Fixed,
+            trigger_price: Some(trigger_price),
+            water_mark: None,
+        }
+    }
+
+    pub fn trailing_amount(amount: Decimal) -> Stop {
+        Stop {
+            kind: StopKind::TrailingAmount(amount),
+            trigger_price: None,
+            water_mark: None,
+        }
+    }
+
+    pub fn trailing_percent(percent: Decimal) -> Stop {
+        Stop {
+            kind: StopKind::TrailingPercent(percent),
+            trigger_price: None,
+            water_mark: None,
+        }
+    }
+
+    /// 检查追踪价差, 价差须大于0, 百分比须小于100
+    pub fn check(&self) -> Result<(), &'static str> {
+        return match self.kind {
+            StopKind::TrailingAmount(amount) if amount <= Decimal::zero() => {
+                Err("invalid trailing amount")
+            }
+            StopKind::TrailingPercent(percent)
+                if percent <= Decimal::zero() || percent >= Decimal::from(100) =>
+            {
+                Err("invalid trailing percent")
+            }
+            _ => Ok(()),
+        };
+    }
+
+    /// 按成交价更新追踪价位及触发价, 返回是否触发
+    pub fn on_trade(&mut self, side: OrderSide, price: Decimal) -> bool {
+        let improved = match side {
+            OrderSide::Buy => self.water_mark.is_none_or(|v| price < v),
+            OrderSide::Sell => self.water_mark.is_none_or(|v| price > v),
+        };
+        let distance = match self.kind {
+            StopKind::Fixed => None,
+            StopKind::TrailingAmount(amount) => Some(amount),
+            StopKind::TrailingPercent(percent) => Some(price * percent / Decimal::from(100)),
+        };
+        if let (true, Some(distance)) = (improved, distance) {
+            self.water_mark = Some(price);
+            let trigger_price = match side {
+                OrderSide::Buy => price + distance,
+                OrderSide::Sell => (price - distance).max(Decimal::zero()),
+            };
+            self.trigger_price = Some(trigger_price.normalize());
+        }
+        return self.triggered(side, price);
+    }
+
+    /// 成交价是否达到触发价
+    pub fn triggered(&self, side: OrderSide, price: Decimal) -> bool {
+        return match (side, self.trigger_price) {
+            (OrderSide::Buy, Some(trigger_price)) => price >= trigger_price,
+            (OrderSide::Sell, Some(trigger_price)) => price <= trigger_price,
+            (_, None) => false,
+        };
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{HashMap, VecDeque};
+
+use rust_decimal::{prelude::Zero, Decimal};
+use serde::Serialize;
+use slab::Slab;
+
+use crate::{Order, Trade};
+
+/// 默认保留的已结束订单数
+pub const DEFAULT_RETENTION: usize = 10000;
+
+/// 订单状态
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum OrderStatus {
+    /// 已接受未成交, 包括未触发的止损单
+    New,
+    PartiallyFilled,
+    Filled,
+    /// 已撤销, 包括部分成交后撤销剩余数量
+    Cancelled,
+    Rejected,
+}
+
+impl OrderStatus {
+    /// 是否已结束
+    pub fn is_terminal(&self) -> bool {
+        return matches!(
+            self,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Rejected
+        );
+    }
+}
+
+/// 订单的一笔成交
+#[derive(Clone, Debug, Serialize)]
+pub struct Fill {
+    pub price: Decimal,
+    pub volumn: Decimal,
+    pub fee: Decimal,
+    pub maker: bool,
+    /// 成交时间, 毫秒
+    pub timestamp: i64,
+}
+
+/// 订单状态记录
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderRecord {
+    /// 最近一次下单 改单或撤单时的订单
+    pub order: Order,
+    pub status: OrderStatus,
+    /// 下单数量, 改单后为已成交数量加新数量
+    pub volumn: Decimal,
+    pub remaining: Decimal,
+    pub filled: Decimal,
+    /// 成交均价, 未成交时为None
+    pub avg_price: Option<Decimal>,
+    /// 拒绝原因
+    pub reason: Option<&'static str>,
+    pub fills: Vec<Fill>,
+}
+
+/// 记录节点, 成交明细按时间先后组成链表
+struct RecordNode {
+    order: Order,
+    status: OrderStatus,
+    volumn: Decimal,
+    remaining: Decimal,
+    filled: Decimal,
+    /// 成交金额
+    amount: Decimal,
+    reason: Option<&'static str>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    /// 结束序号, 与terminal中的序号一致时才淘汰
+    seq: Option<u64>,
+}
+
+struct FillNode {
+    fill: Fill,
+    next: Option<usize>,
+}
+
+/// 订单状态存储, 由订单簿在下单 成交 改单 撤单时更新
+///
+/// 已结束的订单按结束顺序保留retention条, 节点复用已释放的空间
+pub struct OrderStore {
+    retention: usize,
+    records: Slab<RecordNode>,
+    fills: Slab<FillNode>,
+    /// order_id -> 记录下标
+    order_ids: HashMap<u64, usize>,
+    /// 账户 -> 客户订单号 -> 最近使用该客户订单号的order_id
+    client_orders: HashMap<u64, HashMap<String, u64>>,
+    /// 已结束的订单 (order_id, 结束序号), 按结束顺序
+    terminal: VecDeque<(u64, u64)>,
+    seq: u64,
+}
+
+impl Default for OrderStore {
+    fn default() -> Self {
+        OrderStore::new(DEFAULT_RETENTION)
+    }
+}
+
+impl OrderStore {
+    pub fn new(retention: usize) -> OrderStore {
+        OrderStore {
+            retention,
+            records: Slab::new(),
+            fills: Slab::new(),
+            order_ids: HashMap::new(),
+            client_orders: HashMap::new(),
+            terminal: VecDeque::new(),
+            seq: 0,
+        }
+    }
+
+    pub fn retention(&self) -> usize {
+        return self.retention;
+    }
+
+    /// 修改保留的已结束订单数, 超出部分立即淘汰
+    pub fn set_retention(&mut self, retention: usize) {
+        self.retention = retention;
+        self.evict();
+    }
+
+    /// 查看订单状态及成交明细
+    pub fn get(&self, order_id: u64) -> Option<OrderRecord> {
+        let node = &self.records[*self.order_ids.get(&order_id)?];
+        let mut fills = Vec::new();
+        let mut next = node.head;
+        while let Some(index) = next {
+            fills.push(self.fills[index].fill.clone());
+            next = self.fills[index].next;
+        }
+        let avg_price = if node.filled.is_zero() {
+            None
+        } else {
+            Some((node.amount / node.filled).normalize())
+        };
+        return Some(OrderRecord {
+            order: node.order.clone(),
+            status: node.status,
+            volumn: node.volumn,
+            remaining: node.remaining,
+            filled: node.filled,
+            avg_price,
+            reason: node.reason,
+            fills,
+        });
+    }
+
+    /// 是否有该订单号的记录, 包括已结束且仍在保留期内的订单
+    pub fn contains(&self, order_id: u64) -> bool {
+        return self.order_ids.contains_key(&order_id);
+    }
+
+    /// 按账户及客户订单号查找最近使用该客户订单号的订单号
+    pub fn client_order(&self, owner: u64, client_order_id: &str) -> Option<u64> {
+        return self.client_orders.get(&owner)?.get(client_order_id).copied();
+    }
+
+    /// 记录数, 包括未结束的订单
+    pub fn len(&self) -> usize {
+        return self.order_ids.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.order_ids.is_empty();
+    }
+
+    /// 新建记录, 订单号已有记录时覆盖
+    fn insert(&mut self, order: &Order, status: OrderStatus) -> usize {
+        if let Some(index) = self.order_ids.get(&order.id).copied() {
+            self.clear_fills(index);
+            let node = &mut self.records[index];
+            node.order = order.clone();
+            node.status = status;
+            node.volumn = order.volumn;
+            node.remaining = order.volumn;
+            node.filled = Decimal::zero();
+            node.amount = Decimal::zero();
+            node.reason = None;
+            node.seq = None;
+            self.insert_client_order(order);
+            return index;
+        }
+        let index = self.records.insert(RecordNode {
+            order: order.clone(),
+            status,
+            volumn: order.volumn,
+            remaining: order.volumn,
+            filled: Decimal::zero(),
+            amount: Decimal::zero(),
+            reason: None,
+            head: None,
+            tail: None,
+            seq: None,
+        });
+        self.order_ids.insert(order.id, index);
+        self.insert_client_order(order);
+        return index;
+    }
+
+    /// 客户订单号指向新订单, 原订单未结束时(如客户订单号重复被拒绝)保持不变
+    fn insert_client_order(&mut self, order: &Order) {
+        let client_order_id = match &order.client_order_id {
+            Some(client_order_id) => client_order_id,
+            None => return,
+        };
+        let (order_ids, records) = (&self.order_ids, &self.records);
+        let open = |order_id: &u64| {
+            order_ids
+                .get(order_id)
+                .is_some_and(|v| *order_id != order.id && !records[*v].status.is_terminal())
+        };
+        let client_orders = self.client_orders.entry(order.owner).or_default();
+        match client_orders.get_mut(client_order_id.as_str()) {
+            Some(order_id) if open(order_id) => {}
+            Some(order_id) => *order_id = order.id,
+            None => {
+                client_orders.insert(client_order_id.clone(), order.id);
+            }
+        }
+    }
+
+    /// 淘汰记录后移除仍指向该订单的客户订单号
+    fn remove_client_order(&mut self, order: &Order) {
+        let client_order_id = match &order.client_order_id {
+            Some(client_order_id) => client_order_id,
+            None => return,
+        };
+        if let Some(client_orders) = self.client_orders.get_mut(&order.owner) {
+            if client_orders.get(client_order_id.as_str()) == Some(&order.id) {
+                client_orders.remove(client_order_id.as_str());
+            }
+            if client_orders.is_empty() {
+                self.client_orders.remove(&order.owner);
+            }
+        }
+    }
+
+    /// 订单已结束, 超出保留数量时淘汰最早结束的订单
+    fn finish(&mut self, index: usize) {
+        self.seq += 1;
+        let node = &mut self.records[index];
+        node.seq = Some(self.seq);
+        self.terminal.push_back((node.order.id, self.seq));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.terminal.len() > self.retention {
+            let (order_id, seq) = self.terminal.pop_front().unwrap();
+            let index = match self.order_ids.get(&order_id) {
+                Some(index) if self.records[*index].seq == Some(seq) => *index,
+                _ => continue,
+            };
+            self.clear_fills(index);
+            let node = self.records.remove(index);
+            self.order_ids.remove(&order_id);
+            self.remove_client_order(&node.order);
+        }
+    }
+
+    fn clear_fills(&mut self, index: usize) {
+        let node = &mut self.records[index];
+        let mut next = node.head.take();
+        node.tail = None;
+        while let Some(index) = next {
+            next = self.fills.remove(index).next;
+        }
+    }
+
+    pub(crate) fn on_accepted(&mut self, order: &Order) {
+        self.insert(order, OrderStatus::New);
+    }
+
+    /// 拒绝订单, 订单号与已结束的订单重复时不修改该订单的记录
+    pub(crate) fn on_rejected(&mut self, order: &Order, reason: &'static str) {
+        let index = match self.order_ids.get(&order.id).copied() {
+            Some(index) if self.records[index].status.is_terminal() => return,
+            Some(index) => index,
+            None => self.insert(order, OrderStatus::Rejected),
+        };
+        let node = &mut self.records[index];
+        node.status = OrderStatus::Rejected;
+        node.reason = Some(reason);
+        self.finish(index);
+    }
+
+    pub(crate) fn on_trade(&mut self, trade: &Trade) {
+        for (order_id, fee, maker) in [
+            (trade.maker_id, trade.maker_fee, true),
+            (trade.taker_id, trade.taker_fee, false),
+        ] {
+            let index = match self.order_ids.get(&order_id) {
+                Some(index) => *index,
+                None => continue,
+            };
+            let fill = self.fills.insert(FillNode {
+                fill: Fill {
+                    price: trade.price,
+                    volumn: trade.volumn,
+                    fee,
+                    maker,
+                    timestamp: trade.timestamp,
+                },
+                next: None,
+            });
+            let node = &mut self.records[index];
+            match node.tail {
+                Some(tail) => self.fills[tail].next = Some(fill),
+                None => node.head = Some(fill),
+            }
+            node.tail = Some(fill);
+            node.filled += trade.volumn;
+            node.remaining -= trade.volumn;
+            node.amount += trade.price * trade.volumn;
+            node.order.volumn = node.remaining;
+            if node.remaining <= Decimal::zero() {
+                node.status = OrderStatus::Filled;
+                self.finish(index);
+            } else {
+                node.status = OrderStatus::PartiallyFilled;
+            }
+        }
+    }
+
+    /// 撤单, 没有记录的订单(如未生效的子单)新建记录
+    pub(crate) fn on_cancel(&mut self, order: &Order) {
+        let index = match self.order_ids.get(&order.id).copied() {
+            Some(index) if !self.records[index].status.is_terminal() => index,
+            _ => self.insert(order, OrderStatus::Cancelled),
+        };
+        let node = &mut self.records[index];
+        node.order = order.clone();
+        node.remaining -= order.volumn;
+        node.status = OrderStatus::Cancelled;
+        self.finish(index);
+    }
+
+    pub(crate) fn on_amended(&mut self, order: &Order) {
+        if let Some(index) = self.order_ids.get(&order.id) {
+            let node = &mut self.records[*index];
+            node.order = order.clone();
+            node.volumn = node.filled + order.volumn;
+            node.remaining = order.volumn;
+        }
+    }
+}
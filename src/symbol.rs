@@ -0,0 +1,112 @@
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    fmt,
+    ops::Deref,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 已驻留的标的名称, 进程内不释放, 只在添加标的及标的改名时驻留
+static SYMBOLS: OnceLock<RwLock<HashSet<Arc<str>>>> = OnceLock::new();
+
+/// 标的名称, 已驻留的同名标的共享一份内存, clone不分配
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// 已驻留的名称只增加引用计数, 否则单独分配, 不驻留, 释放后归还内存
+    ///
+    /// 外部传入的任意名称不会使驻留表增长
+    pub fn new(symbol: &str) -> Symbol {
+        return Symbol::lookup(symbol).unwrap_or_else(|| Symbol(Arc::from(symbol)));
+    }
+
+    /// 查找已驻留的名称, 不存在时返回None, 不插入
+    pub fn lookup(symbol: &str) -> Option<Symbol> {
+        let symbols = SYMBOLS.get_or_init(Default::default);
+        return symbols.read().unwrap().get(symbol).map(|v| Symbol(v.clone()));
+    }
+
+    /// 驻留名称, 首次出现时分配一次. 只在添加标的及标的改名时调用
+    pub fn intern(symbol: &str) -> Symbol {
+        if let Some(v) = Symbol::lookup(symbol) {
+            return v;
+        }
+        let mut symbols = SYMBOLS.get_or_init(Default::default).write().unwrap();
+        if let Some(v) = symbols.get(symbol) {
+            return Symbol(v.clone());
+        }
+        let v: Arc<str> = Arc::from(symbol);
+        symbols.insert(v.clone());
+        return Symbol(v);
+    }
+
+    pub fn as_str(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(symbol: &str) -> Symbol {
+        return Symbol::new(symbol);
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        return &*self.0 == other;
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        return &*self.0 == *other;
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        return *self.0 == **other;
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(&self.0);
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(&*self.0, f);
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&self.0);
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Symbol, D::Error> {
+        let symbol = String::deserialize(deserializer)?;
+        return Ok(Symbol::new(&symbol));
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{OrderSide, Trade};
+
+/// 默认保留的最近成交数
+pub const DEFAULT_TAPE_CAPACITY: usize = 1000;
+
+/// 公开成交记录, 不含账户及手续费
+#[derive(Clone, Debug, Serialize)]
+pub struct TradeRecord {
+    pub trade_id: u64,
+    pub price: Decimal,
+    pub volumn: Decimal,
+    pub taker_side: OrderSide,
+    pub timestamp: i64,
+}
+
+impl From<&Trade> for TradeRecord {
+    fn from(trade: &Trade) -> Self {
+        TradeRecord {
+            trade_id: trade.trade_id,
+            price: trade.price,
+            volumn: trade.volumn,
+            taker_side: trade.taker_side,
+            timestamp: trade.timestamp,
+        }
+    }
+}
+
+/// 最近成交环形缓冲, 按容量预分配, 写满后覆盖最早的成交
+pub struct TradeTape {
+    capacity: usize,
+    trades: VecDeque<TradeRecord>,
+}
+
+impl Default for TradeTape {
+    fn default() -> Self {
+        TradeTape::new(DEFAULT_TAPE_CAPACITY)
+    }
+}
+
+impl TradeTape {
+    pub fn new(capacity: usize) -> TradeTape {
+        TradeTape {
+            capacity,
+            trades: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+
+    /// 修改容量, 超出部分丢弃最早的成交
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.trades.len() > capacity {
+            self.trades.pop_front();
+        }
+        self.capacity = capacity;
+        self.trades.shrink_to(capacity);
+        self.trades.reserve_exact(capacity - self.trades.len());
+    }
+
+    pub fn len(&self) -> usize {
+        return self.trades.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.trades.is_empty();
+    }
+
+    pub(crate) fn push(&mut self, trade: &Trade) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.trades.len() == self.capacity {
+            self.trades.pop_front();
+        }
+        self.trades.push_back(TradeRecord::from(trade));
+    }
+
+    /// 最近limit笔成交, 按时间倒序
+    pub fn recent(&self, limit: usize) -> Vec<TradeRecord> {
+        return self.trades.iter().rev().take(limit).cloned().collect();
+    }
+}
@@ -0,0 +1,105 @@
+#![allow(clippy::needless_return)]
+//! 全局分配器对整个测试二进制生效, 因此单独成文件
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use match_trade::{Engine, Order, OrderKind, OrderSide};
+use rust_decimal::Decimal;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn record() {
+    if COUNTING.try_with(|v| v.get()).unwrap_or(false) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record();
+        return unsafe { System.alloc(layout) };
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record();
+        return unsafe { System.realloc(ptr, layout, new_size) };
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// 统计当前线程执行func期间的分配次数
+fn count_allocations<F: FnOnce()>(func: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    COUNTING.with(|v| v.set(true));
+    func();
+    COUNTING.with(|v| v.set(false));
+    return ALLOCATIONS.load(Ordering::Relaxed) - before;
+}
+
+fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+}
+
+/// 一轮下单 成交 撤单, 返回 (订单, 撤单id)
+fn round(first_id: u64) -> (Vec<Order>, u64) {
+    let order = |id: u64, kind: OrderKind, side: OrderSide, price: &str, volumn: &str| {
+        Order::new(first_id + id, "BTC/DOGE", dec(price), dec(volumn), kind, side, 0).with_owner(1)
+    };
+    let orders = vec![
+        // 新价位
+        order(0, OrderKind::Limit, OrderSide::Sell, "10", "2"),
+        // 部分成交
+        order(1, OrderKind::Limit, OrderSide::Buy, "10", "1"),
+        // 全部成交并移除价位
+        order(2, OrderKind::IOC, OrderSide::Buy, "10", "1"),
+        order(3, OrderKind::Limit, OrderSide::Sell, "11", "1"),
+        // 市价单吃掉卖一价位
+        order(4, OrderKind::Market, OrderSide::Buy, "0", "1"),
+        // 已有价位排队后撤单
+        order(5, OrderKind::Limit, OrderSide::Buy, "8", "1"),
+    ];
+    return (orders, first_id + 5);
+}
+
+#[test]
+fn test_hot_path_allocations() {
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    engine
+        .add_order(Order::new(1, "BTC/DOGE", dec("12"), dec("100"), OrderKind::Limit, OrderSide::Sell, 0).with_owner(2))
+        .unwrap();
+    engine
+        .add_order(Order::new(2, "BTC/DOGE", dec("8"), dec("100"), OrderKind::Limit, OrderSide::Buy, 0).with_owner(2))
+        .unwrap();
+    let run = |(orders, cancel_id): (Vec<Order>, u64)| {
+        for order in orders {
+            engine.add_order(order).unwrap();
+        }
+        engine
+            .cancel_order("BTC/DOGE", cancel_id, dec("8"), OrderSide::Buy)
+            .unwrap();
+    };
+    // 预热, 各索引达到所需容量, 已结束订单达到保留数量后复用记录空间
+    engine.set_order_retention("BTC/DOGE", 8).unwrap();
+    run(round(100));
+    run(round(200));
+    let orders = round(300);
+    assert_eq!(count_allocations(|| run(orders)), 0);
+    assert!(engine.len() == (1, 1));
+}
@@ -0,0 +1,495 @@
+#![allow(clippy::needless_return)]
+//! 随机指令序列分别交给OrderBook及参考撮合模型, 比较成交 事件及剩余挂单
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use match_trade::{Order, OrderBook, OrderKind, OrderSide, ResultQueue, Trade};
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+
+const SYMBOL: &str = "BTC/USDT";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Accepted(u64),
+    Rejected(u64, &'static str),
+    Trade {
+        maker_id: u64,
+        taker_id: u64,
+        price: Decimal,
+        volumn: Decimal,
+    },
+    Cancelled(u64, Decimal),
+    Amended(u64, Decimal, Decimal),
+    Depth(OrderSide, Decimal, Decimal),
+}
+
+#[derive(Clone, Default)]
+struct EventQueue {
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl EventQueue {
+    fn take(&self) -> Vec<Event> {
+        return std::mem::take(&mut *self.events.lock().unwrap());
+    }
+
+    fn push(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ResultQueue for EventQueue {
+    fn order_accepted(&self, order: &Order) {
+        self.push(Event::Accepted(order.id));
+    }
+
+    fn order_rejected(&self, order: &Order, reason: &'static str) {
+        self.push(Event::Rejected(order.id, reason));
+    }
+
+    fn trade_success(&self, trade: &Trade) {
+        self.push(Event::Trade {
+            maker_id: trade.maker_id,
+            taker_id: trade.taker_id,
+            price: trade.price,
+            volumn: trade.volumn,
+        });
+    }
+
+    fn cancel_order(&self, order: &Order) {
+        self.push(Event::Cancelled(order.id, order.volumn));
+    }
+
+    fn order_amended(&self, order: &Order) {
+        self.push(Event::Amended(order.id, order.price, order.volumn));
+    }
+
+    fn depth_changed(&self, side: OrderSide, price: Decimal, volumn: Decimal) {
+        self.push(Event::Depth(side, price, volumn));
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RestingOrder {
+    id: u64,
+    side: OrderSide,
+    price: i64,
+    volumn: i64,
+    all_or_none: bool,
+}
+
+/// 参考撮合模型, 挂单按到达顺序存放, 每次线性查找最优订单
+///
+/// 按各订单类型约定的行为实现, 不参照OrderBook的实现:
+/// * 限价单按价格时间优先与对手盘成交, 剩余部分挂单
+/// * 市价单按对手盘最优价转为限价单, 剩余部分挂单, 对手盘为空时撤销 (见tests.rs的test_market_order),
+///   最优价不含全部成交或不成交的挂单
+/// * IOC单剩余部分撤销, 可成交数量不足最小成交量时拒绝
+/// * FOK单可成交数量不足时拒绝
+/// * 全部成交或不成交的限价单不能全部成交时不成交, 直接挂单
+/// * 全部成交或不成交的挂单数量超过taker剩余数量时跳过, 继续与之后的挂单成交
+/// * 全部成交或不成交的挂单不计入深度
+///
+/// 可成交数量在挂单副本上实际撮合一次得到
+#[derive(Clone, Default)]
+struct Reference {
+    orders: Vec<RestingOrder>,
+    events: Vec<Event>,
+}
+
+fn opposite(side: OrderSide) -> OrderSide {
+    return match side {
+        OrderSide::Buy => OrderSide::Sell,
+        OrderSide::Sell => OrderSide::Buy,
+    };
+}
+
+/// a价格是否优于b
+fn better(side: OrderSide, a: i64, b: i64) -> bool {
+    return match side {
+        OrderSide::Buy => a > b,
+        OrderSide::Sell => a < b,
+    };
+}
+
+/// taker限价price能否与maker_price成交
+fn crosses(side: OrderSide, price: i64, maker_price: i64) -> bool {
+    return match side {
+        OrderSide::Buy => maker_price <= price,
+        OrderSide::Sell => maker_price >= price,
+    };
+}
+
+impl Reference {
+    /// side方向价格最优, 同价格最早的订单, 不含全部成交或不成交的挂单
+    fn best(&self, side: OrderSide) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (index, order) in self.orders.iter().enumerate() {
+            if order.side != side || order.all_or_none {
+                continue;
+            }
+            if best.is_none_or(|v| better(side, order.price, self.orders[v].price)) {
+                best = Some(index);
+            }
+        }
+        return best;
+    }
+
+    /// 按价格时间优先排序, 与taker限价交叉的对手盘挂单下标
+    fn makers(&self, side: OrderSide, price: i64) -> Vec<usize> {
+        let mut makers: Vec<usize> = (0..self.orders.len())
+            .filter(|v| {
+                let order = &self.orders[*v];
+                order.side == opposite(side) && crosses(side, price, order.price)
+            })
+            .collect();
+        makers.sort_by_key(|v| match side {
+            OrderSide::Buy => self.orders[*v].price,
+            OrderSide::Sell => -self.orders[*v].price,
+        });
+        return makers;
+    }
+
+    /// 数量为volumn的taker可以成交的数量, 在副本上撮合, 不修改挂单
+    fn available(&self, side: OrderSide, price: i64, volumn: i64) -> i64 {
+        let mut rest = volumn;
+        self.clone().sweep(0, side, price, &mut rest);
+        return volumn - rest;
+    }
+
+    fn sweep(&mut self, id: u64, side: OrderSide, price: i64, volumn: &mut i64) {
+        while *volumn > 0 {
+            let rest = *volumn;
+            let index = self
+                .makers(side, price)
+                .into_iter()
+                .find(|v| !self.orders[*v].all_or_none || self.orders[*v].volumn <= rest);
+            let index = match index {
+                Some(index) => index,
+                None => return,
+            };
+            let maker = &mut self.orders[index];
+            let filled = (*volumn).min(maker.volumn);
+            maker.volumn -= filled;
+            *volumn -= filled;
+            self.events.push(Event::Trade {
+                maker_id: maker.id,
+                taker_id: id,
+                price: maker.price.into(),
+                volumn: filled.into(),
+            });
+            if !maker.all_or_none {
+                self.events
+                    .push(Event::Depth(maker.side, maker.price.into(), (-filled).into()));
+            }
+            if maker.volumn == 0 {
+                self.orders.remove(index);
+            }
+        }
+    }
+
+    fn limit(&mut self, id: u64, side: OrderSide, price: i64, mut volumn: i64, all_or_none: bool) -> Result<bool, &'static str> {
+        if !all_or_none || self.available(side, price, volumn) >= volumn {
+            self.sweep(id, side, price, &mut volumn);
+        }
+        if volumn > 0 {
+            self.orders.push(RestingOrder {
+                id,
+                side,
+                price,
+                volumn,
+                all_or_none,
+            });
+            if !all_or_none {
+                self.events
+                    .push(Event::Depth(side, price.into(), volumn.into()));
+            }
+        }
+        return Ok(true);
+    }
+
+    fn add(&mut self, id: u64, command: &Command) -> Result<bool, &'static str> {
+        let (kind, side, price, mut volumn, min_volumn, all_or_none) = match *command {
+            Command::Add { kind, side, price, volumn, min_volumn, all_or_none } => {
+                (kind, side, price, volumn, min_volumn, all_or_none)
+            }
+            _ => unreachable!(),
+        };
+        self.events.push(Event::Accepted(id));
+        match kind {
+            OrderKind::Limit => return self.limit(id, side, price, volumn, all_or_none),
+            OrderKind::Market => match self.best(opposite(side)) {
+                Some(index) => return self.limit(id, side, self.orders[index].price, volumn, false),
+                None => {
+                    self.events.push(Event::Cancelled(id, volumn.into()));
+                    return Err(match side {
+                        OrderSide::Buy => "empty sell queue",
+                        OrderSide::Sell => "empty buy queue",
+                    });
+                }
+            },
+            OrderKind::IOC => {
+                if min_volumn.is_some_and(|v| self.available(side, price, volumn) < v) {
+                    let reason = "min volumn cannot be filled";
+                    self.events.push(Event::Rejected(id, reason));
+                    return Err(reason);
+                }
+                self.sweep(id, side, price, &mut volumn);
+                if volumn > 0 {
+                    self.events.push(Event::Cancelled(id, volumn.into()));
+                }
+                return Ok(true);
+            }
+            OrderKind::FOK => {
+                if self.available(side, price, volumn) >= volumn {
+                    return self.limit(id, side, price, volumn, false);
+                }
+                let reason = "fok order cannot be filled";
+                self.events.push(Event::Rejected(id, reason));
+                return Err(reason);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: u64, price: i64, side: OrderSide) -> Option<RestingOrder> {
+        let index = self
+            .orders
+            .iter()
+            .position(|v| v.id == id && v.side == side && v.price == price)?;
+        let order = self.orders.remove(index);
+        if !order.all_or_none {
+            self.events
+                .push(Event::Depth(side, price.into(), (-order.volumn).into()));
+        }
+        return Some(order);
+    }
+
+    fn cancel(&mut self, id: u64, price: i64, side: OrderSide) -> Option<Decimal> {
+        let order = self.remove(id, price, side)?;
+        self.events.push(Event::Cancelled(id, order.volumn.into()));
+        return Some(order.volumn.into());
+    }
+
+    fn amend(&mut self, id: u64, price: i64, side: OrderSide, new_price: i64, new_volumn: i64) -> Result<bool, &'static str> {
+        if new_price == price {
+            let order = self
+                .orders
+                .iter_mut()
+                .find(|v| v.id == id && v.side == side && v.price == price && new_volumn <= v.volumn);
+            if let Some(order) = order {
+                let old_volumn = order.volumn;
+                order.volumn = new_volumn;
+                if !order.all_or_none {
+                    self.events
+                        .push(Event::Depth(side, price.into(), (new_volumn - old_volumn).into()));
+                }
+                self.events
+                    .push(Event::Amended(id, price.into(), new_volumn.into()));
+                return Ok(true);
+            }
+        }
+        let order = match self.remove(id, price, side) {
+            Some(order) => order,
+            None => return Err("not found order"),
+        };
+        self.events
+            .push(Event::Amended(id, new_price.into(), new_volumn.into()));
+        return self.limit(id, side, new_price, new_volumn, order.all_or_none);
+    }
+
+    /// 按价格时间优先顺序返回side方向挂单 (id, 价格, 数量)
+    fn resting(&self, side: OrderSide) -> Vec<(u64, Decimal, Decimal)> {
+        let mut orders: Vec<&RestingOrder> = self.orders.iter().filter(|v| v.side == side).collect();
+        orders.sort_by_key(|v| match side {
+            OrderSide::Buy => -v.price,
+            OrderSide::Sell => v.price,
+        });
+        return orders
+            .iter()
+            .map(|v| (v.id, v.price.into(), v.volumn.into()))
+            .collect();
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Command {
+    Add {
+        kind: OrderKind,
+        side: OrderSide,
+        price: i64,
+        volumn: i64,
+        min_volumn: Option<i64>,
+        all_or_none: bool,
+    },
+    /// 撤销第index笔下单, 按下单时的价格
+    Cancel { index: usize },
+    Amend {
+        index: usize,
+        price: i64,
+        volumn: i64,
+    },
+}
+
+fn command() -> impl Strategy<Value = Command> {
+    let kind = prop_oneof![
+        4 => Just(OrderKind::Limit),
+        1 => Just(OrderKind::Market),
+        1 => Just(OrderKind::IOC),
+        1 => Just(OrderKind::FOK),
+    ];
+    let side = prop_oneof![Just(OrderSide::Buy), Just(OrderSide::Sell)];
+    return prop_oneof![
+        6 => (kind, side, 1..12i64, 1..10i64, 1..10i64, 0..4).prop_map(|(kind, side, price, volumn, min, flag)| {
+            // 最小成交量只用于IOC单, 全部成交或不成交只用于限价单
+            let min_volumn = if kind == OrderKind::IOC && flag == 0 { Some(min) } else { None };
+            let all_or_none = kind == OrderKind::Limit && flag == 0;
+            Command::Add { kind, side, price, volumn, min_volumn, all_or_none }
+        }),
+        2 => any::<usize>().prop_map(|index| Command::Cancel { index }),
+        1 => (any::<usize>(), 1..12i64, 1..10i64)
+            .prop_map(|(index, price, volumn)| Command::Amend { index, price, volumn }),
+    ];
+}
+
+/// 按价格时间优先顺序返回side方向挂单 (id, 价格, 数量)
+fn resting(book: &OrderBook, side: OrderSide) -> Vec<(u64, Decimal, Decimal)> {
+    let queue = match side {
+        OrderSide::Buy => &book.buy_queue,
+        OrderSide::Sell => &book.sell_queue,
+    };
+    return queue
+        .select(None, None, None)
+        .into_iter()
+        .map(|(id, price)| (id, price, queue.get(id).unwrap().volumn))
+        .collect();
+}
+
+/// 除全部成交或不成交的挂单外盘口不交叉, 价位挂单量与订单及depth事件累计一致,
+/// 全部成交或不成交的挂单不计入价位挂单量及depth事件
+fn check_invariants(book: &OrderBook, depth: &BTreeMap<(u8, Decimal), Decimal>) {
+    let best = |side| {
+        return resting(book, side)
+            .into_iter()
+            .find(|(id, _, _)| !book.get_order(*id).unwrap().all_or_none)
+            .map(|(_, price, _)| price);
+    };
+    if let (Some(bid), Some(ask)) = (best(OrderSide::Buy), best(OrderSide::Sell)) {
+        assert!(bid < ask, "crossed book {} >= {}", bid, ask);
+    }
+    let mut levels = BTreeMap::new();
+    for (key, side) in [(0, OrderSide::Buy), (1, OrderSide::Sell)] {
+        let mut volumns: BTreeMap<Decimal, Decimal> = BTreeMap::new();
+        for (id, price, volumn) in resting(book, side) {
+            assert!(volumn > Decimal::ZERO);
+            if !book.get_order(id).unwrap().all_or_none {
+                *volumns.entry(price).or_default() += volumn;
+            }
+        }
+        let queue = match side {
+            OrderSide::Buy => &book.buy_queue,
+            OrderSide::Sell => &book.sell_queue,
+        };
+        for (price, volumn) in queue.levels() {
+            assert_eq!(volumns.get(&price), Some(&volumn));
+            levels.insert((key, price), volumn);
+        }
+        assert_eq!(volumns.len(), queue.levels().count());
+    }
+    let depth: BTreeMap<_, _> = depth
+        .iter()
+        .filter(|(_, v)| !v.is_zero())
+        .map(|(k, v)| (*k, *v))
+        .collect();
+    assert_eq!(depth, levels);
+}
+
+/// 新订单数量守恒: 成交量 + 挂单量 + 撤销量 = 下单数量, 被拒绝的订单不成交
+fn check_conservation(book: &OrderBook, id: u64, volumn: i64, events: &[Event]) {
+    let mut total = Decimal::ZERO;
+    for event in events {
+        match event {
+            Event::Rejected(order_id, _) if *order_id == id => {
+                assert!(events.iter().all(|v| !matches!(v, Event::Trade { .. })));
+                assert!(book.get_order(id).is_none());
+                return;
+            }
+            Event::Trade { taker_id, volumn, .. } if *taker_id == id => total += volumn,
+            Event::Cancelled(order_id, volumn) if *order_id == id => total += volumn,
+            _ => {}
+        }
+    }
+    if let Some(order) = book.get_order(id) {
+        total += order.volumn;
+    }
+    assert_eq!(total, Decimal::from(volumn));
+}
+
+fn run(commands: Vec<Command>) {
+    let queue = EventQueue::default();
+    let mut book = OrderBook::new(SYMBOL.to_string(), Some(Box::new(queue.clone())));
+    let mut reference = Reference::default();
+    // 已下单 (id, 方向, 价格)
+    let mut placed: Vec<(u64, OrderSide, i64)> = Vec::new();
+    let mut depth: BTreeMap<(u8, Decimal), Decimal> = BTreeMap::new();
+    for command in commands {
+        match command.clone() {
+            Command::Add { kind, side, price, volumn, min_volumn, all_or_none } => {
+                let id = placed.len() as u64 + 1;
+                let order_price = if kind == OrderKind::Market { 0 } else { price };
+                placed.push((id, side, order_price));
+                let mut order = Order::new(id, SYMBOL, price.into(), volumn.into(), kind, side, 0);
+                order.min_volumn = min_volumn.map(Decimal::from);
+                order.all_or_none = all_or_none;
+                let expected = reference.add(id, &command);
+                assert_eq!(book.add_order(order), expected.map(|_| id), "{:?}", command);
+                let events = queue.take();
+                check_conservation(&book, id, volumn, &events);
+                queue.events.lock().unwrap().extend(events);
+            }
+            Command::Cancel { index } => {
+                if placed.is_empty() {
+                    continue;
+                }
+                let (id, side, price) = placed[index % placed.len()];
+                let expected = reference.cancel(id, price, side);
+                let res = book.calcen_order(id, price.into(), side).map(|v| v.volumn);
+                assert_eq!(res, expected, "{:?}", command);
+            }
+            Command::Amend { index, price: new_price, volumn } => {
+                if placed.is_empty() {
+                    continue;
+                }
+                let index = index % placed.len();
+                let (id, side, price) = placed[index];
+                let expected = reference.amend(id, price, side, new_price, volumn);
+                let res = book.amend_order(id, price.into(), side, new_price.into(), volumn.into());
+                assert_eq!(res, expected, "{:?}", command);
+                placed[index].2 = new_price;
+            }
+        }
+        let events = queue.take();
+        for event in &events {
+            if let Event::Depth(side, price, volumn) = event {
+                let key = if *side == OrderSide::Buy { 0 } else { 1 };
+                *depth.entry((key, *price)).or_default() += volumn;
+            }
+        }
+        assert_eq!(events, std::mem::take(&mut reference.events), "{:?}", command);
+        check_invariants(&book, &depth);
+    }
+    for side in [OrderSide::Buy, OrderSide::Sell] {
+        assert_eq!(resting(&book, side), reference.resting(side));
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn test_matches_reference(commands in prop::collection::vec(command(), 1..80)) {
+        run(commands);
+    }
+}
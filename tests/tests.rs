@@ -1,6 +1,7 @@
-use std::{convert::TryInto, str::FromStr};
+#![allow(clippy::needless_return)]
+use std::{collections::BTreeMap, convert::TryInto, str::FromStr, sync::{Arc, Mutex}};
 use chrono::Utc;
-use match_trade::{Engine, Order, OrderKind, OrderSide};
+use match_trade::{CancelFilter, Engine, FeeAsset, FeeRate, FeeSchedule, GroupKind, Instrument, LocalEngine, ManualClock, Order, OrderBook, OrderKind, OrderRef, OrderSide, OrderStatus, Peg, PegKind, ResultQueue, RiskLimits, Scale, Sequencer, ShardedEngine, Stop, Symbol, Trade, TradingStatus};
 use rust_decimal::Decimal;
 
 
@@ -52,6 +53,52 @@ fn test_fok_order(){
     assert!(engine.len()==(10,0));
     create_order(1, OrderKind::FOK, OrderSide::Sell,"101","3000", &mut engine);
     assert!(engine.len()==(10,0));
+    let fok = |id,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),OrderKind::FOK,side,0);
+    assert!(engine.add_order(fok(1,OrderSide::Sell,"100","1001"))==Err("fok order cannot be filled"));
+    assert!(engine.len()==(10,0));
+    engine.add_order(fok(2,OrderSide::Sell,"100","550")).unwrap();
+    assert!(engine.len()==(5,0));
+    engine.add_order(fok(3,OrderSide::Buy,"100","1")).unwrap_err();
+    create_order(2, OrderKind::Limit, OrderSide::Sell,"101","100", &mut engine);
+    engine.add_order(fok(4,OrderSide::Buy,"100","1")).unwrap_err();
+    engine.add_order(fok(5,OrderSide::Buy,"101","200")).unwrap();
+    assert!(engine.len()==(5,0));
+}
+
+#[test]
+fn test_min_volumn_and_all_or_none(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    let order = |id,kind,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),kind,side,0);
+    engine.add_order(order(1,OrderKind::Limit,OrderSide::Sell,"10","5").with_all_or_none()).unwrap();
+    engine.add_order(order(2,OrderKind::Limit,OrderSide::Sell,"10","2")).unwrap();
+    engine.add_order(order(3,OrderKind::Limit,OrderSide::Sell,"11","3")).unwrap();
+    // 跳过数量为5的全部成交挂单
+    engine.add_order(order(4,OrderKind::IOC,OrderSide::Buy,"11","4").with_min_volumn(dec("4"))).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 1).unwrap().volumn==dec("5"));
+    assert!(engine.get_order("BTC/DOGE", 2).is_none());
+    assert!(engine.get_order("BTC/DOGE", 3).unwrap().volumn==dec("1"));
+    assert!(engine.add_order(order(5,OrderKind::IOC,OrderSide::Buy,"10","3").with_min_volumn(dec("1")))==Err("min volumn cannot be filled"));
+    engine.add_order(order(6,OrderKind::Limit,OrderSide::Buy,"10","5")).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 1).is_none());
+    assert!(engine.len()==(0,1));
+    // 只能部分成交的全部成交或不成交订单不成交, 直接挂单
+    engine.add_order(order(7,OrderKind::Limit,OrderSide::Buy,"11","2").with_all_or_none()).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 3).unwrap().volumn==dec("1") && engine.get_order("BTC/DOGE", 7).unwrap().volumn==dec("2"));
+    // 与对手盘交叉的全部成交挂单不计入深度及最优价
+    let depth = engine.depth("BTC/DOGE", 10).unwrap();
+    assert!(depth.bids.is_empty() && depth.asks==vec![(dec("11"),dec("1"))]);
+    assert!(engine.book("BTC/DOGE").unwrap().read().unwrap().buy_queue.first_price().is_none());
+    engine.add_order(order(8,OrderKind::Limit,OrderSide::Buy,"9","2").with_all_or_none()).unwrap();
+    engine.add_order(order(9,OrderKind::IOC,OrderSide::Sell,"9","1")).unwrap();
+    assert!(engine.len()==(2,1));
+    engine.add_order(order(10,OrderKind::IOC,OrderSide::Sell,"9","2")).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 7).is_none() && engine.len()==(1,1));
+    // 可以全部成交时正常成交
+    engine.add_order(order(11,OrderKind::Limit,OrderSide::Buy,"11","1").with_all_or_none()).unwrap();
+    assert!(engine.len()==(1,0));
+    assert!(engine.add_order(order(12,OrderKind::Market,OrderSide::Buy,"0","1").with_all_or_none())==Err("all-or-none requires limit order"));
+    assert!(engine.add_order(order(13,OrderKind::Limit,OrderSide::Buy,"9","1").with_min_volumn(dec("1")))==Err("min volumn requires ioc order"));
 }
 #[test]
 fn test_ioc_order(){
@@ -62,30 +109,762 @@ fn test_ioc_order(){
     assert!(engine.len()==(0,0));
 }
 
+#[test]
+fn test_pegged_orders(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    let order = |id,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),OrderKind::Limit,side,0);
+    let price = |id| engine.get_order("BTC/DOGE", id).unwrap().price;
+    assert!(engine.add_order(order(9,OrderSide::Buy,"0","1").with_peg(Peg::new(PegKind::Primary)))==Err("peg reference price unavailable"));
+    engine.add_order(order(1,OrderSide::Buy,"10","5")).unwrap();
+    engine.add_order(order(2,OrderSide::Sell,"12","5")).unwrap();
+    // 偏移及价格限制须为tick_size整数倍
+    assert!(engine.add_order(order(10,OrderSide::Buy,"0","1").with_peg(Peg::new(PegKind::Primary).with_offset(dec("0.000000001"))))==Err("invalid peg"));
+    assert!(engine.add_order(order(11,OrderSide::Buy,"0","1").with_peg(Peg::new(PegKind::Midpoint).with_limit(dec("11.000000001"))))==Err("invalid peg"));
+    engine.add_order(order(3,OrderSide::Buy,"0","1").with_peg(Peg::new(PegKind::Primary))).unwrap();
+    engine.add_order(order(4,OrderSide::Sell,"0","1").with_peg(Peg::new(PegKind::Market).with_offset(dec("1")))).unwrap();
+    engine.add_order(order(5,OrderSide::Buy,"0","1").with_peg(Peg::new(PegKind::Midpoint).with_limit(dec("10.5")))).unwrap();
+    assert!((price(3),price(4),price(5))==(dec("10"),dec("11"),dec("10.5")));
+    // 挂钩单不作为参考价
+    engine.add_order(order(6,OrderSide::Buy,"10.6","1")).unwrap();
+    assert!((price(3),price(4),price(5))==(dec("10.6"),dec("11.6"),dec("10.5")));
+    engine.cancel_order("BTC/DOGE", 6, dec("10.6"), OrderSide::Buy).unwrap();
+    assert!((price(3),price(4),price(5))==(dec("10"),dec("11"),dec("10.5")));
+    // 重新定价后排在价位末尾
+    engine.add_order(order(7,OrderSide::Sell,"10","2").with_owner(1)).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 5).is_none());
+    assert!(engine.get_order("BTC/DOGE", 1).unwrap().volumn==dec("4"));
+    assert!(engine.get_order("BTC/DOGE", 3).unwrap().volumn==dec("1"));
+    assert!(engine.amend_order("BTC/DOGE", 3, dec("10"), OrderSide::Buy, dec("9"), dec("1"))==Err("cannot amend pegged order price"));
+    engine.amend_order("BTC/DOGE", 4, dec("11"), OrderSide::Sell, dec("11"), dec("0.5")).unwrap();
+    // 卖出挂钩买一价, 立即成交
+    engine.add_order(order(8,OrderSide::Sell,"0","1").with_peg(Peg::new(PegKind::Market))).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 1).unwrap().volumn==dec("3"));
+    assert!(engine.mass_cancel(&CancelFilter::default()).len()==4);
+    assert!(engine.len()==(0,0));
+}
+
+#[test]
+fn test_stop_orders(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    let order = |id,kind,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),kind,side,0);
+    let stop = |id| engine.get_order("BTC/DOGE", id).unwrap().stop.unwrap();
+    for (id,side,price) in [(1,OrderSide::Sell,"10"),(2,OrderSide::Sell,"11"),(3,OrderSide::Sell,"12"),(4,OrderSide::Buy,"9"),(5,OrderSide::Buy,"8")] {
+        engine.add_order(order(id,OrderKind::Limit,side,price,"5")).unwrap();
+    }
+    engine.add_order(order(10,OrderKind::Market,OrderSide::Buy,"0","2").with_stop(Stop::new(dec("11")))).unwrap();
+    engine.add_order(order(11,OrderKind::Market,OrderSide::Sell,"0","1").with_stop(Stop::trailing_amount(dec("1"))).with_owner(1)).unwrap();
+    assert!(stop(11).trigger_price.is_none());
+    engine.add_order(order(20,OrderKind::Limit,OrderSide::Buy,"10","1")).unwrap();
+    assert!(engine.book("BTC/DOGE").unwrap().read().unwrap().last_price()==Some(dec("10")));
+    assert!(stop(11).trigger_price==Some(dec("9")));
+    // 成交价11触发买入止损, 卖出追踪止损触发价上移
+    engine.add_order(order(21,OrderKind::Limit,OrderSide::Buy,"11","5")).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 10).is_none());
+    assert!(engine.get_order("BTC/DOGE", 2).unwrap().volumn==dec("2"));
+    assert!(stop(11).trigger_price==Some(dec("10")));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(11)).unwrap().order.stop.unwrap().trigger_price==Some(dec("10")));
+    assert!(engine.open_orders(None, 1).len()==1);
+    engine.add_order(order(22,OrderKind::Limit,OrderSide::Sell,"9","1")).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 11).is_none());
+    assert!(engine.get_order("BTC/DOGE", 4).unwrap().volumn==dec("3"));
+    engine.add_order(order(12,OrderKind::Limit,OrderSide::Buy,"12","1").with_stop(Stop::trailing_percent(dec("10")))).unwrap();
+    assert!(stop(12).trigger_price==Some(dec("9.9")));
+    assert!(engine.add_order(order(12,OrderKind::Limit,OrderSide::Buy,"12","1")).is_err());
+    // 未触发的止损单可改价格数量, 触发价不变
+    engine.amend_order("BTC/DOGE", 12, dec("12"), OrderSide::Buy, dec("13"), dec("2")).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 12).unwrap().volumn==dec("2") && stop(12).trigger_price==Some(dec("9.9")));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(12)).unwrap().volumn==dec("2"));
+    engine.cancel_order("BTC/DOGE", 12, dec("13"), OrderSide::Buy).unwrap();
+    assert!(engine.add_order(order(14,OrderKind::Market,OrderSide::Sell,"0","1").with_stop(Stop::trailing_amount(dec("0"))))==Err("invalid trailing amount"));
+    assert!(engine.add_order(order(15,OrderKind::Market,OrderSide::Sell,"0","1").with_stop(Stop::trailing_percent(dec("100"))))==Err("invalid trailing percent"));
+    assert!(engine.add_order(order(16,OrderKind::Market,OrderSide::Sell,"0","1").with_stop(Stop::trailing_percent(dec("-1"))))==Err("invalid trailing percent"));
+    assert!(engine.add_order(order(13,OrderKind::IOC,OrderSide::Buy,"12","1").with_stop(Stop::new(dec("11"))))==Err("stop requires limit or market order"));
+}
+
+#[test]
+fn test_order_groups(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    engine.add_book("ETH/DOGE", None).unwrap();
+    let order = |id,kind,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),kind,side,0);
+    let exists = |id| engine.get_order("BTC/DOGE", id).is_some();
+    engine.add_order(order(1,OrderKind::Limit,OrderSide::Sell,"12","5")).unwrap();
+    engine.add_order(order(2,OrderKind::Limit,OrderSide::Buy,"8","5")).unwrap();
+    // 止盈限价单部分成交后撤销止损单
+    let take_profit = order(10,OrderKind::Limit,OrderSide::Sell,"11","2");
+    let stop_loss = order(11,OrderKind::Market,OrderSide::Sell,"0","2").with_stop(Stop::new(dec("9")));
+    assert!(engine.add_oco(vec![take_profit,stop_loss])==Ok(10));
+    assert!(engine.get_order("BTC/DOGE", 11).unwrap().group_id==Some(10));
+    assert!(engine.order_group("BTC/DOGE", 10).unwrap().legs.len()==2);
+    engine.add_order(order(20,OrderKind::Limit,OrderSide::Buy,"11","1")).unwrap();
+    assert!(exists(10) && !exists(11));
+    assert!(engine.order_group("BTC/DOGE", 10).is_none());
+    // 撤销一笔后撤销其余订单
+    engine.add_oco(vec![order(12,OrderKind::Limit,OrderSide::Sell,"13","1"),order(13,OrderKind::Limit,OrderSide::Sell,"14","1")]).unwrap();
+    // 改单失败时订单组数量不变
+    assert!(engine.amend_order("BTC/DOGE", 12, dec("12"), OrderSide::Sell, dec("13"), dec("3"))==Err("not found order"));
+    assert!(engine.order_group("BTC/DOGE", 12).unwrap().legs[0].volumn==dec("1"));
+    engine.amend_order("BTC/DOGE", 12, dec("13"), OrderSide::Sell, dec("13"), dec("3")).unwrap();
+    assert!(engine.order_group("BTC/DOGE", 12).unwrap().legs[0].volumn==dec("3"));
+    engine.cancel_order("BTC/DOGE", 12, dec("13"), OrderSide::Sell).unwrap();
+    assert!(!exists(13));
+    // 父单全部成交后子单组成OCO
+    let parent = order(30,OrderKind::Limit,OrderSide::Buy,"10","2");
+    let children = vec![order(31,OrderKind::Limit,OrderSide::Sell,"15","2"),order(32,OrderKind::Market,OrderSide::Sell,"0","2").with_stop(Stop::new(dec("7")))];
+    engine.add_oto(parent, children).unwrap();
+    assert!(engine.order_group("BTC/DOGE", 30).unwrap().pending.len()==2);
+    engine.add_order(order(40,OrderKind::Limit,OrderSide::Sell,"10","1")).unwrap();
+    assert!(!exists(31) && engine.order_group("BTC/DOGE", 30).unwrap().legs[0].filled==dec("1"));
+    engine.add_order(order(41,OrderKind::Limit,OrderSide::Sell,"10","1")).unwrap();
+    assert!(exists(31) && exists(32));
+    assert!(engine.order_group("BTC/DOGE", 30).unwrap().kind==GroupKind::Oco);
+    engine.cancel_order("BTC/DOGE", 31, dec("15"), OrderSide::Sell).unwrap();
+    assert!(!exists(32));
+    // 父单撤销后子单不再生效
+    engine.add_oto(order(50,OrderKind::Limit,OrderSide::Buy,"9","1"), vec![order(51,OrderKind::Limit,OrderSide::Sell,"15","1")]).unwrap();
+    engine.cancel_order("BTC/DOGE", 50, dec("9"), OrderSide::Buy).unwrap();
+    assert!(engine.order_group("BTC/DOGE", 50).is_none() && !exists(51));
+    let other = Order::new(60,"ETH/DOGE",dec("1"),dec("1"),OrderKind::Limit,OrderSide::Buy,0);
+    assert!(engine.add_oco(vec![order(61,OrderKind::Limit,OrderSide::Buy,"1","1"),other])==Err("order group must share a symbol"));
+    assert!(engine.add_oco(vec![order(62,OrderKind::Limit,OrderSide::Buy,"1","1"),order(62,OrderKind::Limit,OrderSide::Buy,"2","1")])==Err("duplicate order_id"));
+    assert!(engine.len()==(1,2));
+}
+
+#[test]
+fn test_client_order_id(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    let order = |owner,client_order_id,side,price| {
+        Order::new(0,"BTC/DOGE",dec(price),dec("1"),OrderKind::Limit,side,0).with_owner(owner).with_client_order_id(client_order_id)
+    };
+    assert!(engine.add_order(order(1,"a",OrderSide::Buy,"8"))==Ok(1));
+    assert!(engine.add_order(order(1,"b",OrderSide::Buy,"9"))==Ok(2));
+    assert!(engine.find_order("BTC/DOGE", &OrderRef::client(1,"b")).unwrap().id==2);
+    // 指定订单号后从其后分配
+    assert!(engine.add_order(Order::new(10,"BTC/DOGE",dec("12"),dec("1"),OrderKind::Limit,OrderSide::Sell,0))==Ok(10));
+    assert!(engine.add_order(order(2,"a",OrderSide::Sell,"13"))==Ok(11));
+    assert!(engine.find_order("BTC/DOGE", &OrderRef::client(2,"a")).unwrap().id==11);
+    assert!(engine.add_order(order(1,"a",OrderSide::Sell,"14"))==Err("duplicate client_order_id"));
+    assert!(engine.add_order(Order::new(1,"BTC/DOGE",dec("14"),dec("1"),OrderKind::Limit,OrderSide::Sell,0))==Err("duplicate order_id"));
+    // 止损单同样按客户订单号查找
+    let stop = order(1,"c",OrderSide::Sell,"7").with_stop(Stop::new(dec("7")));
+    engine.add_order(stop).unwrap();
+    assert!(engine.find_order("BTC/DOGE", &OrderRef::client(1,"c")).unwrap().id==13);
+    assert!(engine.amend_by_ref("BTC/DOGE", &OrderRef::client(1,"a"), dec("8.5"), dec("2"))==Ok(true));
+    assert!(engine.find_order("BTC/DOGE", &OrderRef::Id(1)).unwrap().price==dec("8.5"));
+    assert!(engine.amend_by_ref("BTC/DOGE", &OrderRef::client(1,"x"), dec("8"), dec("1"))==Err("not found order"));
+    assert!(engine.cancel_by_ref("BTC/DOGE", &OrderRef::client(1,"a")).unwrap().id==1);
+    assert!(engine.cancel_by_ref("BTC/DOGE", &OrderRef::Id(13)).is_some());
+    assert!(engine.cancel_by_ref("BTC/DOGE", &OrderRef::client(1,"a")).is_none());
+    // 订单完成后客户订单号可重复使用, 订单号不能重复使用
+    assert!(engine.add_order(order(1,"a",OrderSide::Buy,"8"))==Ok(14));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::client(1,"a")).unwrap().order.id==14);
+    assert!(engine.add_order(Order::new(1,"BTC/DOGE",dec("7"),dec("1"),OrderKind::Limit,OrderSide::Buy,0))==Err("duplicate order_id"));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(1)).unwrap().status==OrderStatus::Cancelled);
+    // 订单号在Engine内所有标的不重复
+    let queue = RecordQueue::default();
+    engine.add_book("ETH/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    let eth_order = |owner,client_order_id,side| {
+        Order::new(0,"ETH/DOGE",dec("1"),dec("1"),OrderKind::Limit,side,0).with_owner(owner).with_client_order_id(client_order_id)
+    };
+    assert!(engine.add_order(eth_order(3,"m",OrderSide::Buy))==Ok(15));
+    // 成交回报带双方的客户订单号
+    engine.add_order(eth_order(4,"t",OrderSide::Sell)).unwrap();
+    let trade = queue.trades.lock().unwrap()[0].clone();
+    assert!(trade.maker_client_order_id.as_deref()==Some("m") && trade.taker_client_order_id.as_deref()==Some("t"));
+    let oco = vec![order(3,"d",OrderSide::Buy,"1"),order(3,"d",OrderSide::Buy,"2")];
+    assert!(engine.add_oco(oco)==Err("duplicate client_order_id"));
+    assert!(engine.len()==(2,2));
+    // 订单号用尽后不再分配
+    engine.add_order(Order::new(u64::MAX,"BTC/DOGE",dec("15"),dec("1"),OrderKind::Limit,OrderSide::Sell,0)).unwrap();
+    assert!(engine.add_order(order(4,"e",OrderSide::Sell,"16"))==Err("order id exhausted"));
+    assert!(engine.len()==(2,3));
+}
+
+#[test]
+fn test_order_status(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    let order = |id,kind,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),kind,side,0);
+    let status = |id| engine.order_status("BTC/DOGE", &OrderRef::Id(id)).unwrap();
+    engine.add_order(order(1,OrderKind::Limit,OrderSide::Sell,"10","3")).unwrap();
+    engine.add_order(order(2,OrderKind::Limit,OrderSide::Buy,"10","1")).unwrap();
+    assert!(status(1).status==OrderStatus::PartiallyFilled && status(1).remaining==dec("2"));
+    assert!(status(2).status==OrderStatus::Filled && !status(2).fills[0].maker);
+    engine.add_order(order(3,OrderKind::Limit,OrderSide::Sell,"13","2")).unwrap();
+    // IOC单部分成交后撤销剩余数量
+    engine.add_order(order(4,OrderKind::IOC,OrderSide::Buy,"13","5")).unwrap();
+    let record = status(4);
+    assert!(record.status==OrderStatus::Cancelled && record.filled==dec("4") && record.remaining==dec("0"));
+    assert!(record.avg_price==Some(dec("11.5")) && record.fills.len()==2);
+    assert!(status(1).status==OrderStatus::Filled && status(1).fills.len()==2 && status(1).avg_price==Some(dec("10")));
+    let _ = engine.add_order(order(5,OrderKind::FOK,OrderSide::Buy,"10","1"));
+    assert!(status(5).status==OrderStatus::Rejected && status(5).reason==Some("fok order cannot be filled"));
+    // 订单号重复被拒绝时不修改原订单的记录
+    engine.add_order(order(6,OrderKind::Limit,OrderSide::Buy,"8","2")).unwrap();
+    assert!(engine.add_order(order(6,OrderKind::Limit,OrderSide::Sell,"20","1"))==Err("duplicate order_id"));
+    assert!(status(6).status==OrderStatus::New && status(6).order.side==OrderSide::Buy);
+    engine.amend_order("BTC/DOGE", 6, dec("8"), OrderSide::Buy, dec("8"), dec("1")).unwrap();
+    assert!(status(6).volumn==dec("1"));
+    engine.cancel_order("BTC/DOGE", 6, dec("8"), OrderSide::Buy).unwrap();
+    assert!(status(6).status==OrderStatus::Cancelled && status(6).remaining==dec("0"));
+    // 只保留最近结束的订单
+    engine.set_order_retention("BTC/DOGE", 2).unwrap();
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(1)).is_none() && engine.order_status("BTC/DOGE", &OrderRef::Id(4)).is_none());
+    assert!(status(5).status==OrderStatus::Rejected);
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(7)).is_none());
+}
+
+#[test]
+fn test_trade_tape(){
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    engine.set_tape_capacity("BTC/DOGE", 3).unwrap();
+    let order = |id,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),OrderKind::Limit,side,0);
+    for (id, price) in [(1,"10"),(2,"11"),(3,"12")] {
+        engine.add_order(order(id,OrderSide::Sell,price,"1")).unwrap();
+    }
+    // 一笔订单与多个价位成交
+    engine.add_order(order(4,OrderSide::Buy,"11","2")).unwrap();
+    engine.add_order(order(5,OrderSide::Buy,"12","1")).unwrap();
+    engine.add_order(order(6,OrderSide::Buy,"9","1")).unwrap();
+    engine.add_order(order(7,OrderSide::Sell,"9","1")).unwrap();
+    let trades = engine.recent_trades("BTC/DOGE", 10).unwrap();
+    assert!(trades.iter().map(|v| v.trade_id).collect::<Vec<_>>()==vec![4,3,2]);
+    assert!(trades[0].price==dec("9") && trades[0].taker_side==OrderSide::Sell);
+    assert!(trades[1].price==dec("12") && trades[1].taker_side==OrderSide::Buy);
+    assert!(trades.windows(2).all(|v| v[0].timestamp>=v[1].timestamp && v[1].timestamp>0));
+    assert!(engine.recent_trades("BTC/DOGE", 1).unwrap().len()==1);
+    engine.set_tape_capacity("BTC/DOGE", 1).unwrap();
+    assert!(engine.recent_trades("BTC/DOGE", 10).unwrap()[0].trade_id==4);
+    assert!(engine.recent_trades("ETH/DOGE", 10).is_none());
+}
+
+#[test]
+fn test_trade_fee(){
+    let queue = RecordQueue::default();
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    engine.set_fee_schedule("BTC/DOGE", FeeSchedule::new(
+        FeeAsset::Quote,
+        4,
+        FeeRate::new(dec("0.001"), dec("0.002")),
+    ).with_tier(1, FeeRate::new(dec("-0.00015"), dec("0.0015")))).unwrap();
+    engine.set_fee_tier(7, 1);
+    let maker = Order::new(1,"BTC/DOGE",dec("3.33"),dec("1"),OrderKind::Limit,OrderSide::Sell,0).with_owner(7);
+    engine.add_order(maker).unwrap();
+    let taker = Order::new(2,"BTC/DOGE",dec("3.33"),dec("1"),OrderKind::Limit,OrderSide::Buy,0).with_owner(8);
+    engine.add_order(taker).unwrap();
+
+    let trades = queue.trades.lock().unwrap();
+    assert!(trades.len()==1);
+    assert!(trades[0].maker_owner==7 && trades[0].taker_owner==8);
+    // 3.33 * -0.00015 = -0.0004995 返佣向零取整
+    assert!(trades[0].maker_fee==dec("-0.0004"));
+    // 3.33 * 0.002 = 0.00666 收费向上取整
+    assert!(trades[0].taker_fee==dec("0.0067"));
+    assert!(trades[0].fee_asset==FeeAsset::Quote);
+}
+
+#[test]
+fn test_risk_limits(){
+    let engine = create_engine();
+    engine.set_risk_limits(1, RiskLimits{
+        max_volumn:Some(dec("10")),
+        max_notional:Some(dec("1000")),
+        max_open_orders:Some(2),
+        max_position:Some(dec("15")),
+        max_order_rate:None,
+    });
+    let order = |id:u64,side:OrderSide,price:&str,volumn:&str|{
+        Order::new(id,"BTC/DOGE",dec(price),dec(volumn),OrderKind::Limit,side,0).with_owner(1)
+    };
+    assert!(engine.add_order(order(1,OrderSide::Buy,"10","11"))==Err("order volumn exceeds limit"));
+    assert!(engine.add_order(order(2,OrderSide::Buy,"200","10"))==Err("order notional exceeds limit"));
+    engine.add_order(order(3,OrderSide::Buy,"10","8")).unwrap();
+    assert!(engine.add_order(order(4,OrderSide::Buy,"10","8"))==Err("position limit exceeded"));
+    engine.add_order(order(5,OrderSide::Buy,"10","7")).unwrap();
+    assert!(engine.add_order(order(6,OrderSide::Sell,"20","1"))==Err("too many open orders"));
+    // 改单按新价格数量检查, 不计该订单改单前的挂单
+    assert!(engine.amend_order("BTC/DOGE", 3, dec("10"), OrderSide::Buy, dec("10"), dec("11"))==Err("order volumn exceeds limit"));
+    assert!(engine.amend_order("BTC/DOGE", 5, dec("10"), OrderSide::Buy, dec("200"), dec("7"))==Err("order notional exceeds limit"));
+    assert!(engine.amend_by_ref("BTC/DOGE", &OrderRef::Id(5), dec("10"), dec("8"))==Err("position limit exceeded"));
+    assert!(engine.get_order("BTC/DOGE", 5).unwrap().volumn==dec("7"));
+    engine.amend_order("BTC/DOGE", 5, dec("10"), OrderSide::Buy, dec("11"), dec("6")).unwrap();
+    engine.amend_order("BTC/DOGE", 5, dec("11"), OrderSide::Buy, dec("10"), dec("7")).unwrap();
+
+    // 成交后持仓由挂单转为净持仓
+    engine.add_order(Order::new(7,"BTC/DOGE",dec("10"),dec("15"),OrderKind::Limit,OrderSide::Sell,0).with_owner(2)).unwrap();
+    let exposure = engine.exposure("BTC/DOGE", 1).unwrap();
+    assert!(exposure.open_orders==0 && exposure.open_buy.is_zero() && exposure.position==dec("15"));
+    assert!(engine.exposure("BTC/DOGE", 2).unwrap().position==dec("-15"));
+    assert!(engine.add_order(order(8,OrderSide::Buy,"10","1"))==Err("position limit exceeded"));
+    engine.add_order(order(9,OrderSide::Sell,"20","10")).unwrap();
+    engine.cancel_order("BTC/DOGE", 9, dec("20"), OrderSide::Sell).unwrap();
+    let exposure = engine.exposure("BTC/DOGE", 1).unwrap();
+    assert!(exposure.open_orders==0 && exposure.open_sell.is_zero());
+}
+
+#[test]
+fn test_risk_order_rate(){
+    let engine = create_engine();
+    engine.set_default_risk_limits(RiskLimits{max_order_rate:Some(3),..Default::default()});
+    for id in 1..3 {
+        engine.add_order(Order::new(id,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    }
+    // 改单同样计入下单频率
+    engine.amend_order("BTC/DOGE", 1, dec("10"), OrderSide::Buy, dec("10"), dec("2")).unwrap();
+    assert!(engine.amend_order("BTC/DOGE", 1, dec("10"), OrderSide::Buy, dec("11"), dec("2"))==Err("order rate exceeded"));
+    assert!(engine.get_order("BTC/DOGE", 1).unwrap().price==dec("10"));
+    assert!(engine.add_order(Order::new(4,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0))==Err("order rate exceeded"));
+}
+
+#[test]
+fn test_manual_clock(){
+    let clock = Arc::new(ManualClock::new(1000));
+    let engine = Engine::with_clock(clock.clone());
+    engine.add_book("BTC/DOGE", None).unwrap();
+    engine.set_default_risk_limits(RiskLimits{max_order_rate:Some(2),..Default::default()});
+    let order = |id,side| Order::new(id,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,side,0);
+    engine.add_order(order(1,OrderSide::Sell)).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 1).unwrap().accepted_at==Some(1000));
+    clock.advance(500);
+    engine.add_order(order(2,OrderSide::Buy)).unwrap();
+    assert!(engine.recent_trades("BTC/DOGE", 1).unwrap()[0].timestamp==1500);
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(1)).unwrap().fills[0].timestamp==1500);
+    // 下单频率按时钟统计, 推进时钟后重新计数
+    assert!(engine.add_order(order(3,OrderSide::Buy))==Err("order rate exceeded"));
+    clock.advance(500);
+    engine.add_order(order(5,OrderSide::Buy)).unwrap();
+    // 迁移的订单簿改用新Engine的时钟
+    let other = Engine::with_clock(Arc::new(ManualClock::new(5000)));
+    other.insert_book(engine.take_book("BTC/DOGE").unwrap()).unwrap();
+    other.add_order(order(4,OrderSide::Sell)).unwrap();
+    assert!(other.recent_trades("BTC/DOGE", 1).unwrap()[0].timestamp==5000);
+    let sharded = ShardedEngine::with_clock(2, 16, clock.clone());
+    sharded.add_book("BTC/DOGE", None).unwrap();
+    sharded.add_order(order(1,OrderSide::Sell)).unwrap();
+    assert!(sharded.get_order("BTC/DOGE", 1).unwrap().accepted_at==Some(2000));
+}
+
+#[test]
+fn test_mass_cancel(){
+    let engine = create_engine();
+    for (id,owner,side,price) in [(1,1,OrderSide::Buy,"10"),(2,1,OrderSide::Buy,"11"),(3,1,OrderSide::Sell,"20"),(4,2,OrderSide::Buy,"10"),(5,2,OrderSide::Sell,"21")] {
+        engine.add_order(Order::new(id,"BTC/DOGE",dec(price),dec("1"),OrderKind::Limit,side,0).with_owner(owner)).unwrap();
+    }
+    let orders = engine.mass_cancel(&CancelFilter{owner:Some(1),side:Some(OrderSide::Buy),max_price:Some(dec("10")),..Default::default()});
+    assert!(orders.iter().map(|v|v.id).collect::<Vec<_>>()==vec![1]);
+    let orders = engine.mass_cancel(&CancelFilter{owner:Some(1),..Default::default()});
+    assert!(orders.iter().map(|v|v.id).collect::<Vec<_>>()==vec![2,3]);
+    assert!(engine.len()==(1,1));
+    assert!(engine.exposure("BTC/DOGE", 1).unwrap().open_orders==0);
+    let orders = engine.mass_cancel(&CancelFilter{symbol:Some("BTC/DOGE".to_string()),min_price:Some(dec("15")),..Default::default()});
+    assert!(orders.iter().map(|v|v.id).collect::<Vec<_>>()==vec![5]);
+    assert!(engine.mass_cancel(&CancelFilter{symbol:Some("ETH/BTC".to_string()),..Default::default()}).is_empty());
+    assert!(engine.len()==(1,0));
+}
+
+#[test]
+fn test_amend_order(){
+    let queue = RecordQueue::default();
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("5"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    engine.add_order(Order::new(2,"BTC/DOGE",dec("10"),dec("5"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    // 只减少数量保留优先级
+    engine.amend_order("BTC/DOGE", 1, dec("10"), OrderSide::Buy, dec("10"), dec("3")).unwrap();
+    engine.add_order(Order::new(3,"BTC/DOGE",dec("10"),dec("3"),OrderKind::Limit,OrderSide::Sell,0)).unwrap();
+    assert!(queue.trades.lock().unwrap()[0].maker_id==1);
+    // 修改价格后重新撮合
+    engine.amend_order("BTC/DOGE", 2, dec("10"), OrderSide::Buy, dec("9"), dec("6")).unwrap();
+    engine.add_order(Order::new(4,"BTC/DOGE",dec("12"),dec("1"),OrderKind::Limit,OrderSide::Sell,0)).unwrap();
+    engine.amend_order("BTC/DOGE", 4, dec("12"), OrderSide::Sell, dec("9"), dec("1")).unwrap();
+    assert!(queue.trades.lock().unwrap()[1].maker_id==2);
+    assert!(engine.exposure("BTC/DOGE", 0).unwrap().open_buy==dec("5"));
+    engine.amend_order("BTC/DOGE", 9, dec("10"), OrderSide::Buy, dec("10"), dec("1")).unwrap_err();
+    engine.amend_order("BTC/DOGE", 2, dec("9"), OrderSide::Buy, dec("9"), dec("0")).unwrap_err();
+}
+
+#[test]
+fn test_order_events(){
+    let queue = RecordQueue::default();
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    engine.set_risk_limits(0, RiskLimits{max_volumn:Some(dec("10")),..Default::default()});
+    engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("5"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("5"),OrderKind::Limit,OrderSide::Buy,0)).unwrap_err();
+    engine.add_order(Order::new(2,"BTC/DOGE",dec("10"),dec("50"),OrderKind::Limit,OrderSide::Buy,0)).unwrap_err();
+    engine.add_order(Order::new(3,"BTC/DOGE",dec("10"),dec("8"),OrderKind::IOC,OrderSide::Sell,0)).unwrap();
+    engine.add_order(Order::new(4,"BTC/DOGE",dec("8"),dec("2"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    engine.cancel_order("BTC/DOGE", 4, dec("8"), OrderSide::Buy).unwrap();
+    assert!(*queue.events.lock().unwrap()==vec![
+        "accepted 1",
+        "rejected 1 duplicate order_id",
+        "rejected 2 order volumn exceeds limit",
+        "accepted 3",
+        "cancel 3 3",
+        "accepted 4",
+        "cancel 4 2",
+    ]);
+}
+
+#[test]
+fn test_depth_changed(){
+    let queue = RecordQueue::default();
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("5"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    engine.add_order(Order::new(2,"BTC/DOGE",dec("10"),dec("3"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    engine.add_order(Order::new(3,"BTC/DOGE",dec("9"),dec("4"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    engine.add_order(Order::new(4,"BTC/DOGE",dec("9.5"),dec("7"),OrderKind::Limit,OrderSide::Sell,0)).unwrap();
+    engine.add_order(Order::new(5,"BTC/DOGE",dec("11"),dec("2"),OrderKind::Limit,OrderSide::Sell,0)).unwrap();
+    engine.amend_order("BTC/DOGE", 5, dec("11"), OrderSide::Sell, dec("11"), dec("1")).unwrap();
+    engine.add_order(Order::new(6,"BTC/DOGE",dec("12"),dec("1"),OrderKind::IOC,OrderSide::Buy,0)).unwrap();
+    engine.cancel_order("BTC/DOGE", 3, dec("9"), OrderSide::Buy).unwrap();
+    let depth = queue.depth.lock().unwrap();
+    let depth:Vec<_> = depth.iter().filter(|(_,v)|!v.is_zero()).map(|(k,v)|format!("{} {}",k,v)).collect();
+    assert!(depth==vec!["Buy 10 1"]);
+}
+
+#[test]
+fn test_instrument(){
+    let engine = Engine::new();
+    let mut instrument = Instrument::new("ETH/BTC");
+    instrument.tick_size = dec("0.5");
+    instrument.lot_size = dec("0.1");
+    instrument.min_volumn = dec("1");
+    engine.add_instrument(instrument, None).unwrap();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    assert!(engine.symbols().iter().map(|v|v.symbol.as_str()).collect::<Vec<_>>()==vec!["BTC/DOGE","ETH/BTC"]);
+    let order = |id:u64,price:&str,volumn:&str|Order::new(id,"ETH/BTC",dec(price),dec(volumn),OrderKind::Limit,OrderSide::Buy,0);
+    assert!(engine.add_order(order(1,"10.2","1"))==Err("invalid price"));
+    assert!(engine.add_order(order(2,"10.5","1.05"))==Err("invalid volumn"));
+    assert!(engine.add_order(order(3,"10.5","0.9"))==Err("volumn below minimum"));
+    engine.add_order(order(4,"10.5","1.1")).unwrap();
+    engine.set_trading_status("ETH/BTC", TradingStatus::Halted).unwrap();
+    assert!(engine.add_order(order(5,"10.5","1"))==Err("trading halted"));
+    assert!(engine.amend_order("ETH/BTC", 4, dec("10.5"), OrderSide::Buy, dec("10.5"), dec("1"))==Err("trading halted"));
+    engine.cancel_order("ETH/BTC", 4, dec("10.5"), OrderSide::Buy).unwrap();
+    assert!(engine.set_trading_status("LTC/BTC", TradingStatus::Trading).is_err());
+
+    for (field,value,err) in [("tick_size","0","invalid tick_size"),("lot_size","-1","invalid lot_size"),("min_volumn","-1","invalid min_volumn")] {
+        let mut instrument = Instrument::new("LTC/BTC");
+        match field {
+            "tick_size" => instrument.tick_size = dec(value),
+            "lot_size" => instrument.lot_size = dec(value),
+            _ => instrument.min_volumn = dec(value),
+        }
+        assert!(engine.add_instrument(instrument, None).err()==Some(err));
+    }
+    assert!(engine.symbols().len()==2);
+}
+
+#[test]
+fn test_unscalable_order(){
+    let queue = RecordQueue::default();
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    // 价格符合精度但换算为tick时溢出, 受理前拒绝
+    assert!(engine.add_order(Order::new(1,"BTC/DOGE",dec("100000000000"),dec("1"),OrderKind::Limit,OrderSide::Buy,0))==Err("invalid price"));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(1)).unwrap().status==OrderStatus::Rejected);
+    assert!(*queue.events.lock().unwrap()==vec!["rejected 1 invalid price".to_string()]);
+    // 改单失败时原订单保留
+    engine.add_order(Order::new(2,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    assert!(engine.amend_order("BTC/DOGE", 2, dec("10"), OrderSide::Buy, dec("100000000000"), dec("1"))==Err("invalid price"));
+    assert!(engine.get_order("BTC/DOGE", 2).unwrap().price==dec("10"));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(2)).unwrap().status==OrderStatus::New);
+}
+
+#[test]
+fn test_order_queries(){
+    let engine = create_engine();
+    for (id,owner,side,price) in [(1,1,OrderSide::Buy,"10"),(2,2,OrderSide::Buy,"10"),(3,1,OrderSide::Buy,"9"),(4,1,OrderSide::Sell,"12"),(5,2,OrderSide::Sell,"11")] {
+        engine.add_order(Order::new(id,"BTC/DOGE",dec(price),dec("2"),OrderKind::Limit,side,0).with_owner(owner)).unwrap();
+    }
+    let depth = engine.depth("BTC/DOGE", 1).unwrap();
+    assert!(depth.bids==vec![(dec("10"),dec("4"))] && depth.asks==vec![(dec("11"),dec("2"))]);
+    let depth = engine.depth("BTC/DOGE", 10).unwrap();
+    assert!(depth.bids.len()==2 && depth.asks==vec![(dec("11"),dec("2")),(dec("12"),dec("2"))]);
+    let orders = engine.open_orders(None, 1);
+    assert!(orders.iter().map(|v|v.id).collect::<Vec<_>>()==vec![1,3,4]);
+    assert!(engine.open_orders(Some("ETH/BTC"), 1).is_empty());
+    assert!(engine.get_order("BTC/DOGE", 5).unwrap().price==dec("11"));
+    assert!(engine.get_order("BTC/DOGE", 6).is_none());
+}
+
+#[test]
+fn test_book_management(){
+    let queue = RecordQueue::default();
+    let engine = Engine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    assert!(engine.add_book("BTC/DOGE", None).is_err());
+    engine.add_book("ETH/BTC", None).unwrap();
+    for (id,symbol) in [(1,"BTC/DOGE"),(2,"BTC/DOGE"),(3,"ETH/BTC")] {
+        engine.add_order(Order::new(id,symbol,dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    }
+    engine.add_order(Order::new(5,"ETH/BTC",dec("0"),dec("1"),OrderKind::Market,OrderSide::Buy,0).with_stop(Stop::new(dec("20")))).unwrap();
+    engine.add_oto(Order::new(6,"ETH/BTC",dec("5"),dec("1"),OrderKind::Limit,OrderSide::Buy,0),vec![Order::new(7,"ETH/BTC",dec("15"),dec("1"),OrderKind::Limit,OrderSide::Sell,0)]).unwrap();
+    engine.rename_book("ETH/BTC", "ETH/USDT").unwrap();
+    assert!(engine.rename_book("ETH/USDT", "BTC/DOGE").is_err());
+    assert!(engine.get_order("ETH/USDT", 3).unwrap().symbol=="ETH/USDT");
+    assert!(engine.get_order("ETH/USDT", 5).unwrap().symbol=="ETH/USDT");
+    assert!(engine.order_group("ETH/USDT", 6).unwrap().pending[0].symbol=="ETH/USDT");
+    assert!(engine.add_order(Order::new(4,"ETH/BTC",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0))==Err("not found symbol"));
+
+    let orders = engine.remove_book("BTC/DOGE").unwrap();
+    assert!(orders.iter().map(|v|v.id).collect::<Vec<_>>()==vec![1,2]);
+    assert!(queue.events.lock().unwrap().ends_with(&["cancel 1 1".to_string(),"cancel 2 1".to_string()]));
+    assert!(engine.remove_book("BTC/DOGE").is_err());
+    assert!(engine.symbols().iter().map(|v|v.symbol.as_str()).collect::<Vec<_>>()==vec!["ETH/USDT"]);
+    assert!(engine.len()==(2,0));
+}
+
+#[test]
+fn test_symbol_interning(){
+    // 只在添加标的及改名时驻留, 按名称查找及创建订单不驻留
+    let engine = ShardedEngine::new(2, 16);
+    assert!(engine.get_order("INTERN/A", 1).is_none() && engine.set_trading_status("INTERN/A", TradingStatus::Halted)==Err("not found symbol"));
+    let order = Order::new(1,"INTERN/A",dec("1"),dec("1"),OrderKind::Limit,OrderSide::Buy,0);
+    assert!(engine.add_order(order.clone())==Err("not found symbol"));
+    assert!(order.symbol=="INTERN/A" && Symbol::lookup("INTERN/A").is_none());
+    engine.add_book("INTERN/A", None).unwrap();
+    assert!(Symbol::lookup("INTERN/A").is_some());
+    engine.add_order(order).unwrap();
+    let local = Engine::new();
+    local.add_book("INTERN/B", None).unwrap();
+    local.rename_book("INTERN/B", "INTERN/C").unwrap();
+    assert!(Symbol::lookup("INTERN/C").is_some());
+}
+
+#[test]
+fn test_sequencer(){
+    let queue = RecordQueue::default();
+    let mut engine = LocalEngine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    let sequencer = Arc::new(Sequencer::new(engine, 16));
+    // 不等待结果连续提交, 按入队顺序撮合
+    let completions:Vec<_> = (1..=4).map(|id|{
+        sequencer.add_order(Order::new(id,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Sell,0))
+    }).collect();
+    for completion in completions {
+        completion.wait().unwrap().unwrap();
+    }
+    let handles:Vec<_> = (0..4u64).map(|i|{
+        let sequencer = sequencer.clone();
+        std::thread::spawn(move||{
+            sequencer.add_order(Order::new(10+i,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).wait().unwrap()
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap().unwrap();
+    }
+    let trades = queue.trades.lock().unwrap().iter().map(|v|v.maker_id).collect::<Vec<_>>();
+    assert!(trades==vec![1,2,3,4]);
+    assert!(sequencer.add_order(Order::new(20,"ETH/BTC",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).wait().unwrap()==Err("not found symbol"));
+    sequencer.add_order(Order::new(21,"BTC/DOGE",dec("9"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).wait().unwrap().unwrap();
+    assert!(sequencer.cancel_order("BTC/DOGE", 21, dec("9"), OrderSide::Buy).wait().unwrap().is_some());
+    // 命令panic后撮合线程继续执行后续命令
+    assert!(sequencer.execute(|_|panic!("command failed")).wait()==Err::<(),_>("command panicked"));
+    sequencer.add_order(Order::new(22,"BTC/DOGE",dec("9"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).wait().unwrap().unwrap();
+    assert!(sequencer.execute(|engine|engine.book("BTC/DOGE").unwrap().order_store.get(22).unwrap().status).wait()==Ok(OrderStatus::New));
+    sequencer.cancel_order("BTC/DOGE", 22, dec("9"), OrderSide::Buy).wait().unwrap().unwrap();
+    let engine = Arc::try_unwrap(sequencer).ok().unwrap().stop().unwrap();
+    assert!(engine.len()==(0,0));
+}
+
+#[test]
+fn test_local_engine(){
+    let mut engine = LocalEngine::new();
+    engine.add_book("BTC/DOGE", None).unwrap();
+    engine.set_risk_limits(1, RiskLimits{max_volumn:Some(dec("5")),..Default::default()});
+    let order = |id,side,price,volumn| Order::new(id,"BTC/DOGE",dec(price),dec(volumn),OrderKind::Limit,side,0).with_owner(1);
+    // 与Engine使用同一套检查, 订单组任意一笔未通过时整组不下单
+    assert!(engine.add_oco(vec![order(1,OrderSide::Sell,"12","1"),order(2,OrderSide::Sell,"13","6")])==Err("order volumn exceeds limit"));
+    assert!(engine.order_status("BTC/DOGE", &OrderRef::Id(2)).unwrap().status==OrderStatus::Rejected);
+    engine.add_oto(order(3,OrderSide::Buy,"10","1"), vec![order(4,OrderSide::Sell,"12","1")]).unwrap();
+    assert!(engine.order_group("BTC/DOGE", 3).unwrap().pending.len()==1);
+    assert!(engine.amend_by_ref("BTC/DOGE", &OrderRef::Id(3), dec("10"), dec("6"))==Err("order volumn exceeds limit"));
+    engine.amend_by_ref("BTC/DOGE", &OrderRef::Id(3), dec("10"), dec("0.5")).unwrap();
+    assert!(engine.get_order("BTC/DOGE", 3).unwrap().volumn==dec("0.5"));
+    assert!(engine.len()==(1,0));
+}
+
+#[test]
+fn test_sharded_engine(){
+    let queue = RecordQueue::default();
+    let engine = ShardedEngine::new(4, 64);
+    let symbols:Vec<String> = (0..32).map(|i|format!("S{}/USDT",i)).collect();
+    for symbol in &symbols {
+        engine.add_book(symbol, None).unwrap();
+    }
+    assert!(engine.add_book("S0/USDT", None)==Err("duplicate symbol"));
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    let mut used:Vec<usize> = symbols.iter().map(|v|engine.shard_of(v)).collect();
+    used.sort();
+    used.dedup();
+    assert!(used.len()>1);
+    assert!(engine.symbols().len()==33);
+
+    engine.set_fee_tier(7, 1);
+    engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("2"),OrderKind::Limit,OrderSide::Sell,0).with_owner(7)).unwrap();
+    engine.add_order(Order::new(2,"S1/USDT",dec("10"),dec("2"),OrderKind::Limit,OrderSide::Buy,0).with_owner(7)).unwrap();
+    assert!(engine.add_order(Order::new(3,"ETH/BTC",dec("10"),dec("2"),OrderKind::Limit,OrderSide::Buy,0))==Err("not found symbol"));
+    assert!(engine.open_orders(None, 7).len()==2);
+
+    // 迁移后挂单保留, 命令投递到新分片
+    let from = engine.shard_of("BTC/DOGE");
+    let to = (from+1)%engine.shards();
+    engine.move_book("BTC/DOGE", to).unwrap();
+    assert!(engine.shard_of("BTC/DOGE")==to);
+    assert!(engine.move_book("ETH/BTC", to)==Err("not found symbol"));
+    assert!(engine.move_book("BTC/DOGE", 4)==Err("invalid shard"));
+    engine.add_order(Order::new(4,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    assert!(queue.trades.lock().unwrap()[0].maker_id==1);
+    assert!(engine.get_order("BTC/DOGE", 1).unwrap().volumn==dec("1"));
+    assert!(engine.cancel_order("BTC/DOGE", 1, dec("10"), OrderSide::Sell).is_some());
+
+    let metrics = engine.metrics();
+    assert!(metrics[to].symbols["BTC/DOGE"]>=5 && !metrics[from].symbols.contains_key("BTC/DOGE"));
+    assert!(metrics.iter().all(|v|v.pending==0));
+    // 哈希固定, 每次启动分配到相同的分片
+    assert!(symbols[..4].iter().map(|v|engine.shard_of(v)).collect::<Vec<_>>()==vec![3,1,0,3]);
+    // 不存在的标的不计入命令数
+    assert!(engine.depth("ETH/BTC", 1).is_none());
+    assert!(engine.metrics().iter().all(|v|!v.symbols.contains_key("ETH/BTC")));
+    assert!(engine.mass_cancel(&CancelFilter{owner:Some(7),..Default::default()}).len()==1);
+    assert!(engine.len()==(0,0));
+}
+
+#[test]
+fn test_price_levels(){
+    let queue = RecordQueue::default();
+    let mut instrument = Instrument::new("BTC/DOGE");
+    instrument.tick_size = dec("0.5");
+    let mut book = OrderBook::with_instrument(instrument, Some(Box::new(queue.clone())));
+    for (id,price) in [(1,"10"),(2,"10"),(3,"10"),(4,"10.5"),(5,"9.5")] {
+        book.add_order(Order::new(id,"BTC/DOGE",dec(price),dec("2"),OrderKind::Limit,OrderSide::Sell,0)).unwrap();
+    }
+    assert!(book.add_order(Order::new(6,"BTC/DOGE",dec("10.2"),dec("1"),OrderKind::Limit,OrderSide::Sell,0))==Err("invalid price"));
+    // 撤销队列中间的订单后保持先后顺序
+    book.calcen_order(2, dec("10"), OrderSide::Sell).unwrap();
+    assert!(book.calcen_order(3, dec("10.5"), OrderSide::Sell).is_none());
+    book.add_order(Order::new(7,"BTC/DOGE",dec("10"),dec("5"),OrderKind::Limit,OrderSide::Buy,0)).unwrap();
+    let makers:Vec<_> = queue.trades.lock().unwrap().iter().map(|v|(v.maker_id,v.volumn)).collect();
+    assert!(makers==vec![(5,dec("2")),(1,dec("2")),(3,dec("1"))]);
+    assert!(book.depth(10).asks==vec![(dec("10"),dec("1")),(dec("10.5"),dec("2"))]);
+    book.amend_order(4, dec("10.5"), OrderSide::Sell, dec("10.5"), dec("1")).unwrap();
+    assert!(book.depth(1).asks==vec![(dec("10"),dec("1"))] && book.sell_queue.levels().nth(1)==Some((dec("10.5"),dec("1"))));
+    assert!(book.get_order(3).unwrap().volumn==dec("1") && book.sell_queue.len()==2);
+}
+
+#[test]
+fn test_scale(){
+    let scale = Scale::new(dec("0.5"), dec("0.01"));
+    assert!(scale.ticks(dec("10.5"))==Some(21) && scale.ticks(dec("10.2")).is_none());
+    let lots = scale.lots(dec("1.25")).unwrap();
+    assert!(scale.volumn(lots)==dec("1.25"));
+    #[cfg(feature = "fixed-point")]
+    {
+        assert!(lots==125 && scale.lots(dec("1.255")).is_none());
+        let mut instrument = Instrument::new("BTC/DOGE");
+        instrument.lot_size = dec("0.01");
+        let mut book = OrderBook::with_instrument(instrument, None);
+        assert!(book.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("1.255"),OrderKind::Limit,OrderSide::Buy,0))==Err("invalid volumn"));
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_engine(){
+    use futures::{executor::block_on, StreamExt};
+    use match_trade::{AsyncEngine, ExecEvent};
+    let queue = RecordQueue::default();
+    let mut engine = LocalEngine::new();
+    engine.add_book("BTC/DOGE", Some(Box::new(queue.clone()))).unwrap();
+    let engine = AsyncEngine::new(engine, 16);
+    let mut events = engine.events();
+    block_on(async{
+        engine.add_book("ETH/BTC", None).await.unwrap();
+        assert!(engine.add_book("ETH/BTC", None).await==Err("duplicate symbol"));
+        // 先投递后await, 按调用顺序执行
+        let first = engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("2"),OrderKind::Limit,OrderSide::Sell,0));
+        let second = engine.add_order(Order::new(2,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Buy,0));
+        let report = second.await;
+        assert!(report.result==Ok(2));
+        assert!(first.await.events.len()==2);
+        assert!(report.events.iter().any(|v|matches!(v,ExecEvent::Trade{trade,..} if trade.maker_id==1)));
+        let report = engine.add_order(Order::new(1,"BTC/DOGE",dec("10"),dec("1"),OrderKind::Limit,OrderSide::Sell,0)).await;
+        assert!(report.result==Err("duplicate order_id") && matches!(report.events[0],ExecEvent::Rejected(_,"duplicate order_id")));
+        assert!(engine.cancel_order("BTC/DOGE", 1, dec("10"), OrderSide::Sell).await.result==Ok(true));
+        assert!(engine.cancel_order("BTC/DOGE", 1, dec("10"), OrderSide::Sell).await.result==Err("not found order"));
+        assert!(engine.depth("BTC/DOGE", 10).await.unwrap().asks.is_empty());
+        match events.next().await.unwrap() {
+            ExecEvent::Accepted(order) => assert!(order.id==1),
+            _ => panic!("unexpected event"),
+        }
+    });
+    // 原结果队列继续收到事件
+    assert!(queue.trades.lock().unwrap().len()==1);
+}
+
+#[derive(Clone, Default)]
+struct RecordQueue{
+    trades:Arc<Mutex<Vec<Trade>>>,
+    events:Arc<Mutex<Vec<String>>>,
+    depth:Arc<Mutex<BTreeMap<String,Decimal>>>,
+}
+
+impl ResultQueue for RecordQueue {
+    fn order_accepted(&self,order:&Order) {
+        self.events.lock().unwrap().push(format!("accepted {}",order.id));
+    }
+
+    fn order_rejected(&self,order:&Order,reason:&'static str) {
+        self.events.lock().unwrap().push(format!("rejected {} {}",order.id,reason));
+    }
+
+    fn trade_success(&self,trade:&Trade) {
+        self.trades.lock().unwrap().push(trade.clone());
+    }
+
+    fn cancel_order(&self,order:&Order) {
+        self.events.lock().unwrap().push(format!("cancel {} {}",order.id,order.volumn));
+    }
+
+    fn depth_changed(&self,side:OrderSide,price:Decimal,volumn:Decimal) {
+        *self.depth.lock().unwrap().entry(format!("{:?} {}",side,price)).or_default() += volumn;
+    }
+}
+
+fn dec(value:&str)->Decimal{
+    Decimal::from_str(value).unwrap()
+}
 
 fn create_order(count:i64,kind:OrderKind,side:OrderSide,price:&str,volumn:&str,engine:&mut Engine){
-    let now = Utc::now().timestamp_nanos();
+    let now = Utc::now().timestamp_nanos_opt().unwrap();
     for i in  0..count {
         let order = Order::new(
             (now+i).try_into().unwrap(),
             "BTC/DOGE",
             Decimal::from_str(price).unwrap(),
             Decimal::from_str(volumn).unwrap(),
-            kind.clone(),
-            side.clone(),
+            kind,
+            side,
             Utc::now().timestamp_millis(),
         );
-        match engine.add_order(order){
-            Err(e)=>println!("{}",e),
-            Ok(v)=>(),
+        if let Err(e) = engine.add_order(order){
+            println!("{}",e)
         }
     }
 }
 
 fn create_engine()->Engine{
-    let mut engine = Engine::new();
+    let engine = Engine::new();
     {
-        engine.add_book("BTC/DOGE", None);
+        engine.add_book("BTC/DOGE", None).unwrap();
     }
     return  engine
 }
\ No newline at end of file